@@ -0,0 +1,187 @@
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+
+use chrono::Utc;
+use mongodb::bson::{self, doc, Document};
+use uuid::Uuid;
+
+use crate::types::ErrorResponse;
+use crate::types::action_database::{ActionFilter, ActionItem, ActionPriority, ActionStatus};
+use crate::types::action_handler::{ActionQuery, CreateActionBody, UpdateActionBody};
+use crate::types::collaboration_handler::ActionEvent;
+use crate::types::role_database::Role;
+use crate::database::action::ActionDatabase;
+use crate::database::case::CaseDatabase;
+use crate::framework::FrameworkRegistry;
+use crate::service::socket::SocketService;
+use crate::api::middleware_handler::{AuthorizeCaseGuard, AuthorizeClientGuard};
+
+
+// attaches an action item to a control or subcontrol of the case, which has to exist in it.
+#[post("/api/case/<case_id>/action", data = "<data>")]
+pub async fn create_action(
+    guard: AuthorizeCaseGuard,
+    case_database: &State<CaseDatabase>,
+    action_database: &State<ActionDatabase>,
+    socket_service: &State<SocketService>,
+    frameworks: &State<FrameworkRegistry>,
+    case_id: String,
+    data: Json<CreateActionBody>
+) -> Result<Custom<Json<ActionItem>>, Custom<Json<ErrorResponse>>> {
+    guard.require(Role::Editor)?;
+    let data = data.into_inner();
+    if data.title.trim().is_empty() {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("an action item needs a title"))));
+    }
+
+    let case = match case_database.read_case_document(&case_id).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no case found")))),
+        Err(e) => {
+            eprintln!("error reading case by id: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case by id"))))
+        }
+    };
+    let framework = match case.get_str("framework").ok().and_then(|v| frameworks.get(v)) {
+        Some(v) => v,
+        None => return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("the framework of the case is not supported"))))
+    };
+    let ids: Vec<String> = std::iter::once(data.control_id.clone()).chain(data.subcontrol_id.clone()).collect();
+    if !framework.has_item(&case, &ids) {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("no such control or subcontrol in the case"))));
+    }
+
+    let now = Utc::now().timestamp_millis();
+    let action = ActionItem {
+        action_id: Uuid::new_v4().to_string(),
+        case_id: case_id.to_string(),
+        group_id: case.get_str("group_id").unwrap_or_default().to_string(),
+        control_id: data.control_id,
+        subcontrol_id: data.subcontrol_id,
+        title: data.title,
+        owner: data.owner,
+        due_date: data.due_date,
+        status: data.status.unwrap_or(ActionStatus::Open),
+        priority: data.priority.unwrap_or(ActionPriority::Medium),
+        evidence: data.evidence,
+        created_by: guard.user.user_id,
+        created_at: now,
+        updated_at: now
+    };
+    if let Err(e) = action_database.create_action(&action).await {
+        eprintln!("error creating action item: {}", e);
+        return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error creating action item"))));
+    }
+
+    if let Err(e) = socket_service.broadcast(&case_id, None, &ActionEvent::ActionItemCreated(action.clone())).await {
+        eprintln!("error broadcasting action item: {}", e);
+    }
+    Ok(Custom(Status::Created, Json(action)))
+}
+
+#[get("/api/case/<case_id>/action/list?<query..>")]
+pub async fn get_case_actions(_guard: AuthorizeCaseGuard, action_database: &State<ActionDatabase>, case_id: String, query: ActionQuery) -> Result<Custom<Json<Vec<ActionItem>>>, Custom<Json<ErrorResponse>>> {
+    let filter = ActionFilter { case_id: Some(case_id), status: query.status, owner: query.owner, ..Default::default() };
+    read_actions(action_database, &filter).await
+}
+
+// the action items of every case in a group, e.g. everything still open for an owner.
+#[get("/api/group/<group_id>/action/list?<query..>")]
+pub async fn get_group_actions(guard: AuthorizeClientGuard, action_database: &State<ActionDatabase>, group_id: String, query: ActionQuery) -> Result<Custom<Json<Vec<ActionItem>>>, Custom<Json<ErrorResponse>>> {
+    if !guard.user.is_member(&group_id) {
+        return Err(Custom(Status::Forbidden, Json(ErrorResponse::new("not a member of the group"))));
+    }
+    let filter = ActionFilter { group_id: Some(group_id), status: query.status, owner: query.owner, ..Default::default() };
+    read_actions(action_database, &filter).await
+}
+
+#[put("/api/case/<case_id>/action/<action_id>", data = "<data>")]
+pub async fn update_action(
+    guard: AuthorizeCaseGuard,
+    action_database: &State<ActionDatabase>,
+    socket_service: &State<SocketService>,
+    case_id: String,
+    action_id: String,
+    data: Json<UpdateActionBody>
+) -> Result<Custom<Json<ActionItem>>, Custom<Json<ErrorResponse>>> {
+    guard.require(Role::Editor)?;
+    let data = data.into_inner();
+    if data.title.as_ref().is_some_and(|v| v.trim().is_empty()) {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("an action item needs a title"))));
+    }
+    let fields = match update_fields(data) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("error serializing action item: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("internal error"))))
+        }
+    };
+
+    let action = match action_database.update_action(&case_id, &action_id, fields).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no action item found")))),
+        Err(e) => {
+            eprintln!("error updating action item: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error updating action item"))))
+        }
+    };
+
+    if let Err(e) = socket_service.broadcast(&case_id, None, &ActionEvent::ActionItemUpdated(action.clone())).await {
+        eprintln!("error broadcasting action item: {}", e);
+    }
+    Ok(Custom(Status::Ok, Json(action)))
+}
+
+#[delete("/api/case/<case_id>/action/<action_id>")]
+pub async fn delete_action(guard: AuthorizeCaseGuard, action_database: &State<ActionDatabase>, socket_service: &State<SocketService>, case_id: String, action_id: String) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+    guard.require(Role::Editor)?;
+    match action_database.delete_action(&case_id, &action_id).await {
+        Ok(Some(_)) => {
+            if let Err(e) = socket_service.broadcast(&case_id, None, &ActionEvent::ActionItemDeleted { action_id }).await {
+                eprintln!("error broadcasting action item: {}", e);
+            }
+            Ok(Custom(Status::Ok, "successfully deleted action item".into()))
+        },
+        Ok(None) => Err(Custom(Status::NotFound, Json(ErrorResponse::new("no action item found")))),
+        Err(e) => {
+            eprintln!("error deleting action item: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error deleting action item"))))
+        }
+    }
+}
+
+async fn read_actions(action_database: &ActionDatabase, filter: &ActionFilter) -> Result<Custom<Json<Vec<ActionItem>>>, Custom<Json<ErrorResponse>>> {
+    match action_database.read_actions(filter).await {
+        Ok(actions) => Ok(Custom(Status::Ok, Json(actions))),
+        Err(e) => {
+            eprintln!("error reading action items: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading action items"))))
+        }
+    }
+}
+
+// the fields an update sets, always including when it was made. a cleared owner or due date is set to null.
+fn update_fields(data: UpdateActionBody) -> Result<Document, bson::ser::Error> {
+    let mut fields = doc! { "updated_at": Utc::now().timestamp_millis() };
+    if let Some(title) = data.title {
+        fields.insert("title", title);
+    }
+    if let Some(owner) = data.owner {
+        fields.insert("owner", owner);
+    }
+    if let Some(due_date) = data.due_date {
+        fields.insert("due_date", due_date);
+    }
+    if let Some(status) = data.status {
+        fields.insert("status", bson::to_bson(&status)?);
+    }
+    if let Some(priority) = data.priority {
+        fields.insert("priority", bson::to_bson(&priority)?);
+    }
+    if let Some(evidence) = data.evidence {
+        fields.insert("evidence", bson::to_bson(&evidence)?);
+    }
+    Ok(fields)
+}
@@ -4,43 +4,108 @@ use rocket::http::Status;
 use rocket::response::status::Custom;
 use rocket::http::ContentType;
 
-use crate::types::case_database::Case;
-use crate::types::case_handler::{CreateCIS18CaseBody, CreateCaseResponse, RenameCaseBody};
+use chrono::Utc;
+use mongodb::bson::{self, doc, Bson, Document};
+use std::collections::HashSet;
+
+use crate::types::case_database::CaseDocument;
+use crate::types::case_handler::{CreateCaseBody, CreateCaseResponse, ImplementationGroupBody, ImplementationGroupResponse, HistoryQuery, MigrateCaseBody, MigrateCaseResponse, ProjectionResponse, SeedCaseBody, RenameCaseBody, RevertCaseBody, RevertCaseResponse, RevertRejection};
 use crate::types::ErrorResponse;
-use crate::types::case_database::GroupCases;
+use crate::types::case_database::{ChangeOutcome, GroupCases};
+use crate::types::audit_database::{AuditAction, AuditFilter, AuditHistory, AuditRecord, HistoryPoint};
+use crate::types::collaboration_handler::{Change, Event, Message, Reply, TextOrIntValue};
+use crate::types::framework::{CaseOptions, CaseScore};
+use crate::types::crosswalk::ProjectedItem;
+use crate::types::role_database::{Role, RoleAssignment};
+use crate::framework::{cis18, crosswalk, FrameworkRegistry};
+use crate::framework::cis18::CIS18Case;
+use crate::framework::crosswalk::CrosswalkRegistry;
+use crate::database::action::ActionDatabase;
+use crate::database::audit::AuditDatabase;
+use crate::database::comment::CommentDatabase;
+use crate::database::role::RoleDatabase;
 use crate::database::case::CaseDatabase;
-use crate::api::middleware_handler::AuthorizeClientGuard;
+use crate::database::template::TemplateDatabase;
+use crate::service::socket::SocketService;
+use crate::api::middleware_handler::{AuthorizeCaseGuard, AuthorizeClientGuard};
+
+// the largest page of history that can be requested at once.
+const MAX_HISTORY_PAGE_SIZE: i64 = 200;
 
 
-#[post("/api/case/cis18/create", data = "<data>")]
-pub async fn create_cis18_case(
-    _guard: AuthorizeClientGuard,
+#[post("/api/case/<framework>/create", data = "<data>")]
+pub async fn create_case(
+    guard: AuthorizeClientGuard,
     case_database: &State<CaseDatabase>,
-    data: Json<CreateCIS18CaseBody>
+    template_database: &State<TemplateDatabase>,
+    role_database: &State<RoleDatabase>,
+    frameworks: &State<FrameworkRegistry>,
+    framework: &str,
+    data: Json<CreateCaseBody>
 ) -> Result<Custom<Json<CreateCaseResponse>>, Custom<Json<ErrorResponse>>> {
-    match case_database.create_cis18_case(&data.group_id, &data.name, data.implementation_group).await {
-        Ok(result) => match result {
-            Some(case_id) => Ok(Custom(Status::Ok, Json(CreateCaseResponse{case_id}))),
-            None => Err(Custom(Status::NotFound, Json(ErrorResponse{error: format!("no matching template found")})))
-        },
+    let framework = match frameworks.get(framework) {
+        Some(v) => v,
+        None => return Err(Custom(Status::NotFound, Json(ErrorResponse{error: format!("no framework {}", framework)})))
+    };
+    if !guard.user.is_member(&data.group_id) {
+        return Err(Custom(Status::Forbidden, Json(ErrorResponse::new("not a member of the group"))));
+    }
+    let options = CaseOptions { implementation_group: data.implementation_group, keep_out_of_scope: data.keep_out_of_scope };
+    if let Err(e) = framework.validate_options(&options) {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: e})));
+    }
+
+    let template = match template_database.read_template(framework.id(), data.template_version, data.locale.as_ref()).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no matching template found")))),
+        Err(e) => {
+            eprintln!("error reading template: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading template"))))
+        }
+    };
+
+    let case_id = match case_database.create_case(framework, template, &data.group_id, &data.name, &options).await {
+        Ok(v) => v,
         Err(e) => {
             eprintln!("error creating case: {}", e);
-            Err(Custom(Status::InternalServerError, Json(ErrorResponse{error: format!("error creating case: {}", e)})))
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse{error: format!("error creating case: {}", e)})))
         }
-    }
+    };
+    set_owner(case_database, role_database, &case_id, &data.group_id, &guard.user.user_id).await?;
+    Ok(Custom(Status::Ok, Json(CreateCaseResponse{case_id})))
 }
 
 #[post("/api/case/<case_id>/rename", data = "<data>")]
 pub async fn rename_case(
-    _guard: AuthorizeClientGuard,
+    guard: AuthorizeCaseGuard,
     case_database: &State<CaseDatabase>, 
+    audit_database: &State<AuditDatabase>,
     case_id: String, 
     data: Json<RenameCaseBody>
 ) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+    guard.require(Role::Owner)?;
     match case_database.rename_case(&case_id, &data.name).await {
         Ok(result) => {
             match result {
-                Some(_) => Ok(Custom(Status::Ok, "successfully renamed the case".into())),
+                Some(old_name) => {
+                    let record = AuditRecord {
+                        case_id: case_id.to_string(),
+                        user_id: guard.user.user_id,
+                        action: AuditAction::Rename,
+                        path: Some("name".into()),
+                        control_id: None,
+                        subcontrol_id: None,
+                        field: None,
+                        old_value: Some(Bson::String(old_name)),
+                        new_value: Some(Bson::String(data.name.to_string())),
+                        revision: None,
+                        timestamp: Utc::now().timestamp_millis()
+                    };
+                    if let Err(e) = audit_database.create_record(&record).await {
+                        eprintln!("error writing audit record: {}", e);
+                    }
+                    Ok(Custom(Status::Ok, "successfully renamed the case".into()))
+                },
                 None => Ok(Custom(Status::NotFound, "no case found".into()))
             } 
         },
@@ -51,12 +116,343 @@ pub async fn rename_case(
     }
 }
 
+// moves the safeguards of a cis18 case in and out of scope for another implementation group, keeping what was entered on them.
+// the case's editors are told to resync, as the safeguards they see change.
+#[post("/api/case/<case_id>/implementation-group", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn change_implementation_group(
+    guard: AuthorizeCaseGuard,
+    case_database: &State<CaseDatabase>,
+    template_database: &State<TemplateDatabase>,
+    audit_database: &State<AuditDatabase>,
+    socket_service: &State<SocketService>,
+    frameworks: &State<FrameworkRegistry>,
+    case_id: String,
+    data: Json<ImplementationGroupBody>
+) -> Result<Custom<Json<ImplementationGroupResponse>>, Custom<Json<ErrorResponse>>> {
+    guard.require(Role::Editor)?;
+    let mut case = read_cis18_case(case_database, &case_id, "have no implementation groups").await?;
+    if let Some(framework) = frameworks.get("cis18") {
+        let options = CaseOptions { implementation_group: Some(data.implementation_group), ..Default::default() };
+        if let Err(e) = framework.validate_options(&options) {
+            return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: e})));
+        }
+    }
+
+    // safeguards the case has never had come from the template it was created from
+    let template = match template_database.read_template("cis18", case.template_version, case.template_locale.as_ref()).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("the template of the case no longer exists")))),
+        Err(e) => {
+            eprintln!("error reading template: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading template"))))
+        }
+    };
+    let template = match bson::from_document::<CIS18Case>(template) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("error parsing template: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading template"))))
+        }
+    };
+
+    let old_implementation_group = case.implementation_group;
+    cis18::change_implementation_group(&mut case, template, data.implementation_group);
+
+    let fields = match (bson::to_bson(&case.controls), bson::to_bson(&case.hidden_subcontrols)) {
+        (Ok(controls), Ok(hidden_subcontrols)) => doc! {
+            "implementation_group": case.implementation_group,
+            "controls": controls,
+            "hidden_subcontrols": hidden_subcontrols
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("error serializing case: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("internal error"))))
+        }
+    };
+    let revision = match case_database.update_case_structure(&case_id, case.revision, &guard.user.user_id, "the implementation group of the case changed", fields).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::Conflict, Json(ErrorResponse::new("the case changed in the meantime, try again")))),
+        Err(e) => {
+            eprintln!("error updating case: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error updating case"))))
+        }
+    };
+
+    let record = AuditRecord {
+        case_id: case_id.to_string(),
+        user_id: guard.user.user_id,
+        action: AuditAction::ImplementationGroup,
+        path: Some("implementation_group".into()),
+        control_id: None,
+        subcontrol_id: None,
+        field: None,
+        old_value: Some(Bson::Int32(old_implementation_group)),
+        new_value: Some(Bson::Int32(case.implementation_group)),
+        revision: Some(revision),
+        timestamp: Utc::now().timestamp_millis()
+    };
+    if let Err(e) = audit_database.create_record(&record).await {
+        eprintln!("error writing audit record: {}", e);
+    }
+
+    let resync = Reply::ResyncRequired { revision, reason: "the implementation group of the case changed".into() };
+    if let Err(e) = socket_service.broadcast(&case_id, None, &resync).await {
+        eprintln!("error broadcasting resync: {}", e);
+    }
+
+    Ok(Custom(Status::Ok, Json(ImplementationGroupResponse { revision, implementation_group: case.implementation_group })))
+}
+
+// reports what moving a case onto another version of its template changes, and moves it unless it's a dry run.
+// a committed migration tells the case's editors to resync.
+#[post("/api/case/<case_id>/migrate", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn migrate_case(
+    guard: AuthorizeCaseGuard,
+    case_database: &State<CaseDatabase>,
+    template_database: &State<TemplateDatabase>,
+    audit_database: &State<AuditDatabase>,
+    socket_service: &State<SocketService>,
+    frameworks: &State<FrameworkRegistry>,
+    case_id: String,
+    data: Json<MigrateCaseBody>
+) -> Result<Custom<Json<MigrateCaseResponse>>, Custom<Json<ErrorResponse>>> {
+    if !data.dry_run {
+        guard.require(Role::Editor)?;
+    }
+    let case = match case_database.read_case_by_id(case_id.to_string()).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no case found")))),
+        Err(e) => {
+            eprintln!("error reading case by id: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case by id"))))
+        }
+    };
+    let framework = match frameworks.get(case.framework()) {
+        Some(v) => v,
+        None => return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: format!("{} cases are not supported", case.framework())})))
+    };
+
+    let (from_version, case_locale) = case.template();
+    let case_locale = case_locale.map(String::from);
+    let locale = data.locale.as_ref().or(case_locale.as_ref()).cloned();
+    let template = match template_database.read_template(framework.id(), data.template_version, locale.as_ref()).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no template found")))),
+        Err(e) => {
+            eprintln!("error reading template: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading template"))))
+        }
+    };
+    // a case and template without versions are taken to be the same as well, unless the locale changes
+    let to_version = template.get_i32("version").ok();
+    if to_version == from_version && locale == case_locale {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("the case already follows this template"))));
+    }
+
+    let revision = case.revision();
+    let (mut fields, report) = match framework.migrate(case, template) {
+        Ok(v) => v,
+        Err(e) => return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: e})))
+    };
+    if data.dry_run {
+        return Ok(Custom(Status::Ok, Json(MigrateCaseResponse { dry_run: true, revision, from_version, to_version, report })));
+    }
+
+    fields.insert("template_version", to_version);
+    fields.insert("template_locale", locale);
+    let new_revision = match case_database.update_case_structure(&case_id, revision, &guard.user.user_id, "the case was migrated to another template version", fields).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::Conflict, Json(ErrorResponse::new("the case changed in the meantime, try again")))),
+        Err(e) => {
+            eprintln!("error updating case: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error updating case"))))
+        }
+    };
+
+    // the report is kept with the record, as removed items only live on in the case's retired items
+    let record = AuditRecord {
+        case_id: case_id.to_string(),
+        user_id: guard.user.user_id,
+        action: AuditAction::Migrate,
+        path: Some("template_version".into()),
+        control_id: None,
+        subcontrol_id: None,
+        field: None,
+        old_value: Some(from_version.map(Bson::Int32).unwrap_or(Bson::Null)),
+        new_value: bson::to_bson(&report).ok(),
+        revision: Some(new_revision),
+        timestamp: Utc::now().timestamp_millis()
+    };
+    if let Err(e) = audit_database.create_record(&record).await {
+        eprintln!("error writing audit record: {}", e);
+    }
+
+    let resync = Reply::ResyncRequired { revision: new_revision, reason: "the case was migrated to another template version".into() };
+    if let Err(e) = socket_service.broadcast(&case_id, None, &resync).await {
+        eprintln!("error broadcasting resync: {}", e);
+    }
+
+    Ok(Custom(Status::Ok, Json(MigrateCaseResponse { dry_run: false, revision: new_revision, from_version, to_version, report })))
+}
+
+// projects the results of a cis18 case onto another framework through the crosswalk between them.
+#[get("/api/case/<case_id>/crosswalk/<target>")]
+pub async fn get_case_projection(
+    _guard: AuthorizeCaseGuard,
+    case_database: &State<CaseDatabase>,
+    crosswalks: &State<CrosswalkRegistry>,
+    case_id: String,
+    target: &str
+) -> Result<Custom<Json<ProjectionResponse>>, Custom<Json<ErrorResponse>>> {
+    let (_, items) = project_case(case_database, crosswalks, &case_id, target).await?;
+    Ok(Custom(Status::Ok, Json(ProjectionResponse { source: "cis18".into(), target: target.to_string(), items })))
+}
+
+// creates a case of another framework in the group of a cis18 case, pre-filled from the results of the cis18 case.
+#[post("/api/case/<case_id>/crosswalk/<target>/seed", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn seed_case(
+    guard: AuthorizeCaseGuard,
+    case_database: &State<CaseDatabase>,
+    template_database: &State<TemplateDatabase>,
+    role_database: &State<RoleDatabase>,
+    frameworks: &State<FrameworkRegistry>,
+    crosswalks: &State<CrosswalkRegistry>,
+    case_id: String,
+    target: &str,
+    data: Json<SeedCaseBody>
+) -> Result<Custom<Json<CreateCaseResponse>>, Custom<Json<ErrorResponse>>> {
+    let framework = match frameworks.get(target) {
+        Some(v) => v,
+        None => return Err(Custom(Status::NotFound, Json(ErrorResponse{error: format!("no framework {}", target)})))
+    };
+    let (group_id, items) = project_case(case_database, crosswalks, &case_id, target).await?;
+
+    let mut template = match template_database.read_template(framework.id(), data.template_version, data.locale.as_ref()).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no matching template found")))),
+        Err(e) => {
+            eprintln!("error reading template: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading template"))))
+        }
+    };
+    if let Err(e) = framework.seed(&mut template, &items) {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: e})));
+    }
+
+    let case_id = match case_database.create_case(framework, template, &group_id, &data.name, &CaseOptions::default()).await {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("error creating case: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse{error: format!("error creating case: {}", e)})))
+        }
+    };
+    set_owner(case_database, role_database, &case_id, &group_id, &guard.user.user_id).await?;
+    Ok(Custom(Status::Ok, Json(CreateCaseResponse{case_id})))
+}
+
+// makes the user who created a case its owner. a case nobody owns can't be shared, renamed or deleted by anyone
+// but an admin, so the case is deleted again if it can't be given its owner.
+async fn set_owner(case_database: &CaseDatabase, role_database: &RoleDatabase, case_id: &String, group_id: &String, user_id: &String) -> Result<(), Custom<Json<ErrorResponse>>> {
+    let owner = RoleAssignment { case_id: case_id.to_string(), group_id: group_id.to_string(), user_id: user_id.to_string(), role: Role::Owner };
+    let e = match role_database.set_role(&owner).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e
+    };
+    eprintln!("error setting owner of case: {}", e);
+    if let Err(e) = case_database.delete_case(case_id).await {
+        eprintln!("error deleting case without owner: {}", e);
+    }
+    Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error creating case"))))
+}
+
+// reads a cis18 case and projects it onto the target framework, returning its group along with the projection.
+async fn project_case(case_database: &CaseDatabase, crosswalks: &CrosswalkRegistry, case_id: &str, target: &str) -> Result<(String, Vec<ProjectedItem>), Custom<Json<ErrorResponse>>> {
+    let case = read_cis18_case(case_database, case_id, "can't be projected onto other frameworks").await?;
+    let crosswalk = match crosswalks.get("cis18", target) {
+        Some(v) => v,
+        None => return Err(Custom(Status::NotFound, Json(ErrorResponse{error: format!("no crosswalk from cis18 to {}", target)})))
+    };
+    let items = crosswalk::project(crosswalk, &case);
+    Ok((case.group_id, items))
+}
+
+// reads a case that has to be a cis18 case, as what is done with it only applies to cis18. the reason completes the
+// error given for cases of other frameworks.
+async fn read_cis18_case(case_database: &CaseDatabase, case_id: &str, reason: &str) -> Result<CIS18Case, Custom<Json<ErrorResponse>>> {
+    let case = match case_database.read_case_by_id(case_id.to_string()).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no case found")))),
+        Err(e) => {
+            eprintln!("error reading case by id: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case by id"))))
+        }
+    };
+    if case.framework() != "cis18" {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: format!("{} cases {}", case.framework(), reason)})));
+    }
+    match bson::from_document::<CIS18Case>(case) {
+        Ok(v) => Ok(v),
+        Err(e) => {
+            eprintln!("error parsing cis18 case: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case by id"))))
+        }
+    }
+}
+
 #[delete("/api/case/<case_id>/delete")]
-pub async fn delete_case(_guard: AuthorizeClientGuard, case_database: &State<CaseDatabase>, case_id: String) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+#[allow(clippy::too_many_arguments)]
+pub async fn delete_case(
+    guard: AuthorizeCaseGuard,
+    case_database: &State<CaseDatabase>,
+    audit_database: &State<AuditDatabase>,
+    action_database: &State<ActionDatabase>,
+    comment_database: &State<CommentDatabase>,
+    role_database: &State<RoleDatabase>,
+    socket_service: &State<SocketService>,
+    case_id: String
+) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+    guard.require(Role::Owner)?;
     match case_database.delete_case(&case_id).await {
         Ok(result) => {
             match result {
-                Some(_) => Ok(Custom(Status::Ok, "successfully deleted case".into())),
+                Some(case) => {
+                    // clients still editing the case are told it is gone rather than failing on their next change
+                    socket_service.close_case(&case_id, "the case was deleted").await;
+
+                    // the audit records are kept, along with the record of the deletion itself
+                    let record = AuditRecord {
+                        case_id: case_id.to_string(),
+                        user_id: guard.user.user_id,
+                        action: AuditAction::Delete,
+                        path: None,
+                        control_id: None,
+                        subcontrol_id: None,
+                        field: None,
+                        old_value: bson::to_bson(&case).ok(),
+                        new_value: None,
+                        revision: None,
+                        timestamp: Utc::now().timestamp_millis()
+                    };
+                    if let Err(e) = audit_database.create_record(&record).await {
+                        eprintln!("error writing audit record: {}", e);
+                    }
+                    if let Err(e) = action_database.delete_actions_by_case(&case_id).await {
+                        eprintln!("error deleting action items: {}", e);
+                    }
+                    if let Err(e) = comment_database.delete_comments_by_case(&case_id).await {
+                        eprintln!("error deleting comments: {}", e);
+                    }
+                    if let Err(e) = role_database.delete_roles_by_case(&case_id).await {
+                        eprintln!("error deleting roles: {}", e);
+                    }
+                    if let Err(e) = case_database.delete_changes_by_case(&case_id).await {
+                        eprintln!("error deleting change log: {}", e);
+                    }
+                    Ok(Custom(Status::Ok, "successfully deleted case".into()))
+                },
                 None => Ok(Custom(Status::NotFound, "no case found".into()))
             }
         },
@@ -67,8 +463,145 @@ pub async fn delete_case(_guard: AuthorizeClientGuard, case_database: &State<Cas
     }
 }
 
+#[get("/api/case/<case_id>/history?<query..>")]
+pub async fn get_case_history(_guard: AuthorizeCaseGuard, audit_database: &State<AuditDatabase>, case_id: String, query: HistoryQuery) -> Result<Custom<Json<AuditHistory>>, Custom<Json<ErrorResponse>>> {
+    let page = query.page.unwrap_or(0);
+    let page_size = query.page_size.unwrap_or(50).clamp(1, MAX_HISTORY_PAGE_SIZE);
+    let filter = AuditFilter {
+        user_id: query.user_id,
+        control_id: query.control_id,
+        from: query.from,
+        to: query.to
+    };
+    match audit_database.read_history(&case_id, &filter, page, page_size).await {
+        Ok(history) => Ok(Custom(Status::Ok, Json(history))),
+        Err(e) => {
+            eprintln!("error reading case history: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case history"))))
+        }
+    }
+}
+
+// restores the fields of a case to the values they had at a revision or point in time, or a single field if given.
+// the restored values are applied as new changes, so they are audited and broadcasted to connected editors.
+#[post("/api/case/<case_id>/revert", data = "<data>")]
+pub async fn revert_case(
+    guard: AuthorizeCaseGuard,
+    case_database: &State<CaseDatabase>,
+    audit_database: &State<AuditDatabase>,
+    socket_service: &State<SocketService>,
+    frameworks: &State<FrameworkRegistry>,
+    case_id: String,
+    data: Json<RevertCaseBody>
+) -> Result<Custom<Json<RevertCaseResponse>>, Custom<Json<ErrorResponse>>> {
+    guard.require(Role::Editor)?;
+    let point = match (data.revision, data.timestamp) {
+        (Some(revision), None) => HistoryPoint::Revision(revision),
+        (None, Some(timestamp)) => HistoryPoint::Timestamp(timestamp),
+        _ => return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("either a revision or a timestamp is required"))))
+    };
+
+    let framework = match case_database.read_case_framework(&case_id).await {
+        Ok(framework) => match frameworks.get(&framework) {
+            Some(v) => v,
+            None => return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: format!("reverting is not supported for {} cases", framework)})))
+        },
+        Err(e) => {
+            eprintln!("error reading case framework: {}", e);
+            return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no case found"))))
+        }
+    };
+
+    let path = match &data.field {
+        Some(target) => match framework.locate(&target.ids, &target.field) {
+            Some(location) => Some(location.path()),
+            None => return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: format!("{} is not a field of a {} case", target.field, framework.id())})))
+        },
+        None => None
+    };
+    let changes = match audit_database.read_changes_after(&case_id, &point, path.as_ref()).await {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("error reading case history: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case history"))))
+        }
+    };
+
+    let mut reverted = Vec::new();
+    let mut rejected = Vec::new();
+    let mut seen = HashSet::new();
+
+    // the value a field had at the point is the old value of the first change made to it afterwards
+    for record in changes {
+        let path = match &record.path {
+            Some(v) => v.to_string(),
+            None => continue
+        };
+        if !seen.insert(path.to_string()) {
+            continue;
+        }
+
+        let (ids, field) = match (record.control_id, record.subcontrol_id, record.field) {
+            (Some(control_id), subcontrol_id, Some(field)) => (std::iter::once(control_id).chain(subcontrol_id).collect(), field),
+            _ => {
+                rejected.push(RevertRejection { path, reason: "change is missing its target".into() });
+                continue;
+            }
+        };
+        let value = match record.old_value.and_then(|v| bson::from_bson::<TextOrIntValue>(v).ok()) {
+            Some(v) => v,
+            None => {
+                rejected.push(RevertRejection { path, reason: "no previous value to restore".into() });
+                continue;
+            }
+        };
+
+        let event = match &value {
+            TextOrIntValue::String(_) => Event::TextChange,
+            TextOrIntValue::Number(_) => Event::DropdownChange,
+            TextOrIntValue::Documentation(_) => Event::DocumentationChange
+        };
+        let event = event(Change { ids, field, value: Some(value), operations: None });
+
+        // revert on top of whatever the case looks like right now
+        let revision = match case_database.read_case_revision(&case_id).await {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("error reading case revision: {}", e);
+                return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reverting case"))))
+            }
+        };
+        let message = Message { event, revision };
+
+        match case_database.update_content(framework, &case_id, &guard.user.user_id, &message).await {
+            Ok(ChangeOutcome::Applied { record, old_value, new_value }) => {
+                if let Err(e) = audit_database.create_change_record(AuditAction::Revert, &record, old_value, new_value).await {
+                    eprintln!("error writing audit record: {}", e);
+                }
+                if let Err(e) = socket_service.broadcast(&case_id, None, &record).await {
+                    eprintln!("error broadcasting change: {}", e);
+                }
+                reverted.push(path);
+            },
+            Ok(ChangeOutcome::Rejected { reason, .. } | ChangeOutcome::Stale { reason, .. }) => rejected.push(RevertRejection { path, reason }),
+            Err(e) => {
+                eprintln!("error reverting case field: {}", e);
+                return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reverting case"))))
+            }
+        }
+    }
+
+    match case_database.read_case_revision(&case_id).await {
+        Ok(revision) => Ok(Custom(Status::Ok, Json(RevertCaseResponse { revision: revision.unwrap_or_default(), reverted, rejected }))),
+        Err(e) => {
+            eprintln!("error reading case revision: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case revision"))))
+        }
+    }
+}
+
 #[get("/api/case/<case_id>")]
-pub async fn get_case(_guard: AuthorizeClientGuard, case_database: &State<CaseDatabase>, case_id: &str) -> Result<Custom<Json<Case>>, Custom<Json<ErrorResponse>> > {
+pub async fn get_case(_guard: AuthorizeCaseGuard, case_database: &State<CaseDatabase>, case_id: &str) -> Result<Custom<Json<Document>>, Custom<Json<ErrorResponse>> > {
     match case_database.read_case_by_id(case_id.into()).await {
         Ok(r) => {
             match r {
@@ -83,8 +616,35 @@ pub async fn get_case(_guard: AuthorizeClientGuard, case_database: &State<CaseDa
     }
 }
 
+// per-control and overall scores of a case, how far they are from their targets and the largest gaps.
+#[get("/api/case/<case_id>/score")]
+pub async fn get_case_score(_guard: AuthorizeCaseGuard, case_database: &State<CaseDatabase>, frameworks: &State<FrameworkRegistry>, case_id: &str) -> Result<Custom<Json<CaseScore>>, Custom<Json<ErrorResponse>>> {
+    let case = match case_database.read_case_by_id(case_id.to_string()).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no case found")))),
+        Err(e) => {
+            eprintln!("error reading case by id: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case by id"))))
+        }
+    };
+    let framework = match frameworks.get(case.framework()) {
+        Some(v) => v,
+        None => return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: format!("{} cases can't be scored", case.framework())})))
+    };
+    match framework.score(&case) {
+        Ok(score) => Ok(Custom(Status::Ok, Json(score))),
+        Err(e) => {
+            eprintln!("error scoring case: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error scoring case"))))
+        }
+    }
+}
+
 #[post("/api/case/list", data = "<group_ids>")]
-pub async fn get_cases(_guard: AuthorizeClientGuard, case_database: &State<CaseDatabase>, group_ids: Json<Vec<&str>>) -> Result<Custom<Json<Vec<GroupCases>>>, Custom<Json<ErrorResponse>>> {
+pub async fn get_cases(guard: AuthorizeClientGuard, case_database: &State<CaseDatabase>, group_ids: Json<Vec<&str>>) -> Result<Custom<Json<Vec<GroupCases>>>, Custom<Json<ErrorResponse>>> {
+    if let Some(group_id) = group_ids.iter().find(|group_id| !guard.user.is_member(group_id)) {
+        return Err(Custom(Status::Forbidden, Json(ErrorResponse{error: format!("not a member of group {}", group_id)})));
+    }
     match case_database.read_cases_sorted_by_group(group_ids.to_vec()).await {
         Ok(cases) => Ok(Custom(Status::Ok, Json(cases))),
         Err(e) => {
@@ -95,17 +655,22 @@ pub async fn get_cases(_guard: AuthorizeClientGuard, case_database: &State<CaseD
 }
 
 #[get("/api/case/<case_id>/export/docx")]
-pub async fn export_case_docx(_guard: AuthorizeClientGuard, case_database: &State<CaseDatabase>, case_id: &str, client: &State<reqwest::Client>) -> Result<(ContentType, Vec<u8>), Custom<Json<ErrorResponse>>> {
+pub async fn export_case_docx(_guard: AuthorizeCaseGuard, case_database: &State<CaseDatabase>, frameworks: &State<FrameworkRegistry>, case_id: &str, client: &State<reqwest::Client>) -> Result<(ContentType, Vec<u8>), Custom<Json<ErrorResponse>>> {
 
     let case = match case_database.read_case_by_id(case_id.to_string()).await {
-        Ok(data) => data,
+        Ok(Some(data)) => data,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no case found")))),
         Err(e) => {
             eprintln!("error reading case by id: {}", e);
             return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case by id"))))
         }
     };
+    let framework = match frameworks.get(case.framework()) {
+        Some(v) => v,
+        None => return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: format!("exporting is not supported for {} cases", case.framework())})))
+    };
 
-    let json_parsed = match serde_json::to_string(&case) {
+    let json_parsed = match framework.export(&case).and_then(|v| serde_json::to_string(&v)) {
         Ok(v) => v,
         Err(e) => {
             eprintln!("error serializing case data: {}", e);
@@ -1,51 +1,66 @@
 use rocket_ws as ws;
+use rocket_ws::frame::{CloseCode, CloseFrame};
 use rocket::State;
-use rocket::futures::StreamExt;
-use std::sync::Arc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::futures::{SinkExt, StreamExt};
+use std::time::{Duration, Instant};
 
-use crate::api::middleware_handler::AuthorizeClientGuard;
+use crate::api::middleware_handler::{AuthorizeCaseGuard, AuthorizeSocketGuard};
+use crate::database::audit::AuditDatabase;
 use crate::database::case::CaseDatabase;
+use crate::database::role::RoleDatabase;
+use crate::framework::FrameworkRegistry;
 use crate::service::socket::SocketService;
-use crate::service::user::UserService;
-use crate::types::collaboration_handler::Message;
+use crate::service::ticket::{TicketService, TICKET_TTL};
+use crate::types::audit_database::AuditAction;
+use crate::types::case_database::{CaseDocument, ChangeOutcome};
+use crate::types::collaboration_handler::{ErrorCode, Event, Message, Reply, TicketResponse};
+use crate::types::role_database::Role;
 
 
+// the most changes sent to a resyncing client before sending it the whole case instead.
+const MAX_RESYNC_CHANGES: i64 = 500;
 
-#[get("/api/collaboration/case/<case_id>/connect/<user_id>?<token>")]
+// how long the role of a connected user is trusted before it is read again, so a role taken away applies to open
+// connections too.
+const ROLE_TTL: Duration = Duration::from_secs(30);
+
+
+
+// issues a ticket to connect to the case with, see AuthorizeSocketGuard.
+#[post("/api/collaboration/case/<case_id>/ticket")]
+pub async fn create_ticket(guard: AuthorizeCaseGuard, ticket_service: &State<TicketService>, case_id: String) -> Custom<Json<TicketResponse>> {
+    let ticket = ticket_service.issue(&guard.user, &case_id).await;
+    Custom(Status::Ok, Json(TicketResponse { ticket, expires_in: TICKET_TTL.as_secs() }))
+}
+
+// the connection belongs to the user the ticket was issued to.
+// every member of the case's group can follow the case, changes are only taken from editors and owners.
+#[get("/api/collaboration/case/<case_id>/connect")]
+#[allow(clippy::too_many_arguments)]
 pub async fn connect<'a>(
-    _guard: AuthorizeClientGuard,
+    guard: AuthorizeSocketGuard,
     case_database: &'a State<CaseDatabase>,
-    user_service: &'a State<Arc<UserService>>,
+    audit_database: &'a State<AuditDatabase>,
     socket_service: &'a State<SocketService>,
+    frameworks: &'a State<FrameworkRegistry>,
+    role_database: &'a State<RoleDatabase>,
     ws: ws::WebSocket,
-    case_id: String,
-    user_id: String,
-    token: String
+    case_id: String
 ) -> ws::Channel<'a> {
-   
-    println!("client connected to case {}", case_id);
 
-    match user_service.check(token).await {
-        Ok(_) => println!("token verified for collaboration access"),
+    // the case is sent to the client as soon as it connects, and tells how to handle received changes
+    let case = match case_database.read_case_by_id(case_id.to_string()).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return refuse(ws, CloseCode::Policy, "no case found"),
         Err(e) => {
-            eprintln!("error checking client token: {}", e);
-            return ws.channel(move |_stream| Box::pin(async { Ok(()) }));
-        }
-    };
-
-    // check if there is already a user connected to the case
-    if socket_service.active_client(&case_id).await {
-        return ws.channel(move |_stream| Box::pin(async { Ok(()) }));
-    }
-
-    // find out what type of case it is, so it is possible to decide how to handle received changes
-    let framework = match case_database.read_case_framework(&case_id).await {
-        Ok(v) => v,
-        Err(e) => {
-            eprintln!("error reading case framework: {}", e);
-            return ws.channel(move |_stream| Box::pin(async { Ok(()) }));
+            eprintln!("error reading case: {}", e);
+            return refuse(ws, CloseCode::Error, "error reading case");
         }
     };
+    let framework = frameworks.get(case.framework());
     
     let ws = ws.config(ws::Config {
         ..Default::default()
@@ -55,33 +70,120 @@ pub async fn connect<'a>(
         
         let (sender, mut receiver) = stream.split();
 
-        socket_service.add_client(&user_id, sender).await;
-        println!("added client connection to registry");
+        // the first frame is the case itself, sent once the connection is registered. changes landing between
+        // reading the case and registering show up as a gap in the revisions of the following broadcasts, which
+        // the client resyncs from.
+        let snapshot = match serde_json::to_string(&Reply::Snapshot { revision: case.revision(), case }) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("error serializing case snapshot: {}", e);
+                return Ok(());
+            }
+        };
+
+        // register the connection, this fails if the case has reached its limit of editors
+        let connection_id = match socket_service.add_client(&case_id, &guard.user.user_id, sender, snapshot).await {
+            Some(id) => id,
+            None => return Ok(())
+        };
 
-        socket_service.add_client_to_case_list(&case_id, &user_id).await;
-        println!("added client to case list");
+        // the role the connection was authorized with, read again once it is older than ROLE_TTL
+        let mut role = guard.role;
+        let mut role_read_at = Instant::now();
 
         while let Some(message) = receiver.next().await {
             match message {
                 Ok(msg) => {
                     if let rocket_ws::Message::Text(v) = msg {
                         match serde_json::from_str::<Message>(&v) {
+                            Ok(Message { event: Event::Focus(focus), .. }) => {
+                                if let Err(e) = socket_service.set_focus(&connection_id, focus).await {
+                                    eprintln!("error broadcasting focus: {}", e);
+                                }
+                            },
+                            Ok(Message { event: Event::Resync, revision }) => {
+                                let resynced = match resync(case_database, &case_id, revision).await {
+                                    Ok(v) => v,
+                                    Err(e) => {
+                                        eprintln!("error resyncing client: {}", e);
+                                        Reply::Error { code: ErrorCode::InternalError, message: "error reading case".into(), revision: None }
+                                    }
+                                };
+                                reply(socket_service, &connection_id, resynced).await;
+                            },
+                            Ok(Message { event: Event::Restructure(_), .. }) => {
+                                reply(socket_service, &connection_id, Reply::Error { code: ErrorCode::InvalidMessage, message: "only the server restructures cases".into(), revision: None }).await;
+                            },
                             Ok(parsed) => {
-                                if framework == "cis18" {
-                                    match case_database.update_cis18_content(&case_id, &parsed).await {
-                                        Ok(_) => {
-                                            println!("updated cis18 content!");
-                                            ()
+                                if role_read_at.elapsed() >= ROLE_TTL {
+                                    match role_database.read_role(&case_id, &guard.user.user_id).await {
+                                        Ok(v) => {
+                                            role = v;
+                                            role_read_at = Instant::now();
                                         },
                                         Err(e) => {
-                                            eprintln!("error updating cis18 case: {}", e);
-                                            break;
+                                            eprintln!("error reading role: {}", e);
+                                            reply(socket_service, &connection_id, Reply::Error { code: ErrorCode::InternalError, message: "error reading role".into(), revision: None }).await;
+                                            continue;
                                         }
                                     }
                                 }
-                                // then the message should be broadcasted here
+                                if role < Role::Editor {
+                                    reply(socket_service, &connection_id, Reply::Error {
+                                        code: ErrorCode::ReadOnly,
+                                        message: "your role on the case doesn't allow changing it".into(),
+                                        revision: None
+                                    }).await;
+                                    continue;
+                                }
+
+                                let framework = match framework {
+                                    Some(v) => v,
+                                    None => {
+                                        reply(socket_service, &connection_id, Reply::Error {
+                                            code: ErrorCode::Unsupported,
+                                            message: "changes are not supported for this case".into(),
+                                            revision: None
+                                        }).await;
+                                        continue;
+                                    }
+                                };
+
+                                let (record, old_value, new_value) = match case_database.update_content(framework, &case_id, &guard.user.user_id, &parsed).await {
+                                    Ok(ChangeOutcome::Applied { record, old_value, new_value }) => (record, old_value, new_value),
+                                    Ok(ChangeOutcome::Rejected { revision, reason }) => {
+                                        println!("rejected change on case {}: {}", case_id, reason);
+                                        reply(socket_service, &connection_id, Reply::Error { code: ErrorCode::InvalidChange, message: reason, revision: Some(revision) }).await;
+                                        continue;
+                                    },
+                                    Ok(ChangeOutcome::Stale { revision, reason }) => {
+                                        println!("stale change on case {}: {}", case_id, reason);
+                                        reply(socket_service, &connection_id, Reply::ResyncRequired { revision, reason }).await;
+                                        continue;
+                                    },
+                                    Err(e) => {
+                                        eprintln!("error updating {} case: {}", framework.id(), e);
+                                        reply(socket_service, &connection_id, Reply::Error { code: ErrorCode::InternalError, message: "error updating case".into(), revision: None }).await;
+                                        continue;
+                                    }
+                                };
+                                println!("updated {} content to revision {}", framework.id(), record.revision);
+
+                                if let Err(e) = audit_database.create_change_record(AuditAction::Change, &record, old_value, new_value).await {
+                                    eprintln!("error writing audit record: {}", e);
+                                }
+
+                                reply(socket_service, &connection_id, Reply::Ack { revision: record.revision }).await;
+
+                                // let the other clients on the case know about the change
+                                if let Err(e) = socket_service.broadcast(&case_id, Some(&connection_id), &record).await {
+                                    eprintln!("error broadcasting change: {}", e);
+                                }
                             },
-                            Err(e) => eprintln!("error parsing received message: {}", e)
+                            Err(e) => {
+                                eprintln!("error parsing received message: {}", e);
+                                reply(socket_service, &connection_id, Reply::Error { code: ErrorCode::InvalidMessage, message: e.to_string(), revision: None }).await;
+                            }
                         }
                     } else if let rocket_ws::Message::Close(_) = msg {
                         println!("client disconnected")
@@ -93,11 +195,45 @@ pub async fn connect<'a>(
                 }
             }
         }
-        socket_service.remove_client(user_id, case_id).await;
+        socket_service.remove_client(&connection_id).await;
         Ok(())
     }))
 }
 
+// the changes made since the client's revision, or the whole case if the change log can't bring the client up to date.
+async fn resync(case_database: &CaseDatabase, case_id: &String, revision: Option<i64>) -> Result<Reply, Box<dyn std::error::Error + Send + Sync>> {
+    let case = match case_database.read_case_by_id(case_id.to_string()).await? {
+        Some(v) => v,
+        None => return Err("no case found".into())
+    };
+    let latest = case.revision();
 
+    if let Some(revision) = revision.filter(|v| (latest - MAX_RESYNC_CHANGES..=latest).contains(v)) {
+        let changes = case_database.read_changes_since(case_id, revision, latest, None).await?;
 
+        // every revision in between has to be a change in the log, otherwise the client would miss something
+        let complete = changes.len() as i64 == latest - revision && changes.iter()
+            .enumerate()
+            .all(|(i, change)| change.revision == revision + 1 + i as i64 && change.event.change().is_some());
+        if complete {
+            let revision = revision + changes.len() as i64;
+            return Ok(Reply::Changes { revision, changes });
+        }
+    }
+    Ok(Reply::Snapshot { revision: latest, case })
+}
 
+async fn reply(socket_service: &SocketService, connection_id: &String, reply: Reply) {
+    if let Err(e) = socket_service.send(connection_id, &reply).await {
+        eprintln!("error sending reply: {}", e);
+    }
+}
+
+// accepts the websocket only to close it again straight away, telling the client why.
+fn refuse<'a>(ws: ws::WebSocket, code: CloseCode, reason: &'static str) -> ws::Channel<'a> {
+    ws.channel(move |mut stream| Box::pin(async move {
+        let frame = CloseFrame { code, reason: reason.into() };
+        stream.send(ws::Message::Close(Some(frame))).await?;
+        Ok(())
+    }))
+}
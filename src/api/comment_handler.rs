@@ -0,0 +1,111 @@
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::types::ErrorResponse;
+use crate::types::case_database::CaseDocument;
+use crate::types::collaboration_handler::{CommentEvent, MAX_TEXT_LENGTH};
+use crate::types::comment_database::Comment;
+use crate::types::comment_handler::CreateCommentBody;
+use crate::types::role_database::Role;
+use crate::database::case::CaseDatabase;
+use crate::database::comment::CommentDatabase;
+use crate::framework::FrameworkRegistry;
+use crate::service::socket::SocketService;
+use crate::api::middleware_handler::AuthorizeCaseGuard;
+
+
+// leaves a comment on an item of the case, which has to exist in it.
+#[post("/api/case/<case_id>/comment", data = "<data>")]
+pub async fn create_comment(
+    guard: AuthorizeCaseGuard,
+    case_database: &State<CaseDatabase>,
+    comment_database: &State<CommentDatabase>,
+    socket_service: &State<SocketService>,
+    frameworks: &State<FrameworkRegistry>,
+    case_id: String,
+    data: Json<CreateCommentBody>
+) -> Result<Custom<Json<Comment>>, Custom<Json<ErrorResponse>>> {
+    guard.require(Role::Commenter)?;
+    let data = data.into_inner();
+    if data.text.trim().is_empty() {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("a comment can't be empty"))));
+    }
+    if data.text.chars().count() > MAX_TEXT_LENGTH {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse{error: format!("a comment can be at most {} characters", MAX_TEXT_LENGTH)})));
+    }
+
+    let case = match case_database.read_case_document(&case_id).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no case found")))),
+        Err(e) => {
+            eprintln!("error reading case by id: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading case by id"))))
+        }
+    };
+    let framework = match frameworks.get(case.framework()) {
+        Some(v) => v,
+        None => return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("the framework of the case is not supported"))))
+    };
+    if !framework.has_item(&case, &data.ids) {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("no such item in the case"))));
+    }
+
+    let comment = Comment {
+        comment_id: Uuid::new_v4().to_string(),
+        case_id: case_id.to_string(),
+        ids: data.ids,
+        user_id: guard.user.user_id,
+        text: data.text,
+        created_at: Utc::now().timestamp_millis()
+    };
+    if let Err(e) = comment_database.create_comment(&comment).await {
+        eprintln!("error creating comment: {}", e);
+        return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error creating comment"))));
+    }
+
+    if let Err(e) = socket_service.broadcast(&case_id, None, &CommentEvent::CommentAdded(comment.clone())).await {
+        eprintln!("error broadcasting comment: {}", e);
+    }
+    Ok(Custom(Status::Created, Json(comment)))
+}
+
+#[get("/api/case/<case_id>/comment/list")]
+pub async fn get_comments(_guard: AuthorizeCaseGuard, comment_database: &State<CommentDatabase>, case_id: String) -> Result<Custom<Json<Vec<Comment>>>, Custom<Json<ErrorResponse>>> {
+    match comment_database.read_comments(&case_id).await {
+        Ok(comments) => Ok(Custom(Status::Ok, Json(comments))),
+        Err(e) => {
+            eprintln!("error reading comments: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading comments"))))
+        }
+    }
+}
+
+// a comment can be deleted by whoever wrote it, and by the owners of the case.
+#[delete("/api/case/<case_id>/comment/<comment_id>")]
+pub async fn delete_comment(guard: AuthorizeCaseGuard, comment_database: &State<CommentDatabase>, socket_service: &State<SocketService>, case_id: String, comment_id: String) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+    let comment = match comment_database.read_comment(&case_id, &comment_id).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(Custom(Status::NotFound, Json(ErrorResponse::new("no comment found")))),
+        Err(e) => {
+            eprintln!("error reading comment: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading comment"))))
+        }
+    };
+    if comment.user_id != guard.user.user_id {
+        guard.require(Role::Owner)?;
+    }
+
+    if let Err(e) = comment_database.delete_comment(&case_id, &comment_id).await {
+        eprintln!("error deleting comment: {}", e);
+        return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error deleting comment"))));
+    }
+    if let Err(e) = socket_service.broadcast(&case_id, None, &CommentEvent::CommentDeleted { comment_id }).await {
+        eprintln!("error broadcasting comment: {}", e);
+    }
+    Ok(Custom(Status::Ok, "successfully deleted comment".into()))
+}
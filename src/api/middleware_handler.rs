@@ -1,54 +1,36 @@
 use std::sync::Arc;
 use rocket::{fairing::{Fairing, Info, Kind}, Data, Request, State};
 use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
 use rocket::request::FromRequest;
 use rocket::request::Outcome;
 use rocket::async_trait;
 
 
+use crate::database::case::CaseDatabase;
+use crate::database::role::RoleDatabase;
+use crate::service::ticket::TicketService;
 use crate::service::user::UserService;
 use crate::types::ErrorResponse;
+use crate::types::case_database::CaseMetadata;
+use crate::types::role_database::Role;
+use crate::types::user_service::User;
 
 
 
-pub struct AuthorizeClientGuard;
+// verifies the client's token, passed as a bearer token in the authorization header, and holds the user it belongs to.
+pub struct AuthorizeClientGuard {
+    pub user: User
+}
 #[async_trait]
 impl<'r> FromRequest<'r> for AuthorizeClientGuard {
     type Error = ErrorResponse;
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-       
-        let user_service = match request.guard::<&State<Arc<UserService>>>().await {
-            Outcome::Success(user_service) => user_service,
-            Outcome::Error(_) | Outcome::Forward(_) => {
-                return Outcome::Error((Status::InternalServerError, ErrorResponse{error: "internal_server_error".into()}));
-            }
-        };
-
-        // check if request is for ws connection, this should be handled differently.
-        // this can't use auth through headers, so instead uses query value.
-        // allow access directly to handler, takes care of it..
-        if let Some(route) = request.route() {
-            if route.uri.path().to_string() == "/api/collaboration/case/<case_id>/connect/<user_id>" {
-                return Outcome::Success(AuthorizeClientGuard);
-            }
-        }
-
-        // if it is a common request, look for the token in the header
         match request.headers().get_one("Authorization") {
             Some(header) => {
-                if header.starts_with("Bearer ") {
-                    let token = &header[7..];
-                    match user_service.check(token.to_string()).await {
-                        Ok(_) => {
-                            Outcome::Success(AuthorizeClientGuard)
-                        },
-                        Err(e) => {
-                            eprintln!("invalid token: {}", e);
-                            Outcome::Error((Status::Unauthorized, ErrorResponse {
-                                error: "invalid token".into() 
-                            }))
-                        },
-                    }
+                if let Some(token) = header.strip_prefix("Bearer ") {
+                    Self::verify(request, token.to_string()).await
                 } else {
                     Outcome::Error((Status::Unauthorized, ErrorResponse {
                         error: "invalid token format".into() 
@@ -62,6 +44,171 @@ impl<'r> FromRequest<'r> for AuthorizeClientGuard {
     }
 }
 
+impl AuthorizeClientGuard {
+    // the user the token belongs to, as verified by the user service.
+    async fn verify(request: &Request<'_>, token: String) -> Outcome<Self, ErrorResponse> {
+        let user_service = match request.guard::<&State<Arc<UserService>>>().await {
+            Outcome::Success(user_service) => user_service,
+            Outcome::Error(_) | Outcome::Forward(_) => {
+                return Outcome::Error((Status::InternalServerError, ErrorResponse{error: "internal_server_error".into()}));
+            }
+        };
+        match user_service.check(token).await {
+            Ok(user) => Outcome::Success(AuthorizeClientGuard { user }),
+            Err(e) => {
+                eprintln!("invalid token: {}", e);
+                Outcome::Error((Status::Unauthorized, ErrorResponse {
+                    error: "invalid token".into() 
+                }))
+            },
+        }
+    }
+}
+
+// verifies the client's token like AuthorizeClientGuard, and that the client is a member of the admin group,
+// ADMIN_GROUP, for routes changing what all groups share, e.g. templates.
+pub struct AuthorizeAdminGuard {
+    pub user: User
+}
+#[async_trait]
+impl<'r> FromRequest<'r> for AuthorizeAdminGuard {
+    type Error = ErrorResponse;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match request.guard::<AuthorizeClientGuard>().await {
+            Outcome::Success(guard) => guard.user,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(status) => return Outcome::Forward(status)
+        };
+        let user_service = match request.guard::<&State<Arc<UserService>>>().await {
+            Outcome::Success(user_service) => user_service,
+            Outcome::Error(_) | Outcome::Forward(_) => {
+                return Outcome::Error((Status::InternalServerError, ErrorResponse{error: "internal_server_error".into()}));
+            }
+        };
+        match user_service.is_admin(&user) {
+            true => Outcome::Success(AuthorizeAdminGuard { user }),
+            false => Outcome::Error((Status::Forbidden, ErrorResponse::new("not a member of the admin group")))
+        }
+    }
+}
+
+// verifies the client's token like AuthorizeClientGuard, and that the client is a member of the group of the case
+// named by the case_id segment of the route. holds the user along with the case and the user's role on it,
+// routes doing more than reading the case check the role they need.
+pub struct AuthorizeCaseGuard {
+    pub user: User,
+    pub case: CaseMetadata,
+    pub role: Role
+}
+#[async_trait]
+impl<'r> FromRequest<'r> for AuthorizeCaseGuard {
+    type Error = ErrorResponse;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<AuthorizeClientGuard>().await {
+            Outcome::Success(guard) => Self::authorize(request, guard.user).await,
+            Outcome::Error(e) => Outcome::Error(e),
+            Outcome::Forward(status) => Outcome::Forward(status)
+        }
+    }
+}
+
+impl AuthorizeCaseGuard {
+    // checks that a verified user can reach the case named by the route, and with which role.
+    async fn authorize(request: &Request<'_>, user: User) -> Outcome<Self, ErrorResponse> {
+        let (case_database, role_database) = match (request.guard::<&State<CaseDatabase>>().await, request.guard::<&State<RoleDatabase>>().await) {
+            (Outcome::Success(case_database), Outcome::Success(role_database)) => (case_database, role_database),
+            _ => {
+                return Outcome::Error((Status::InternalServerError, ErrorResponse{error: "internal_server_error".into()}));
+            }
+        };
+
+        let case_id = match Self::case_id(request) {
+            Some(v) => v,
+            None => return Outcome::Error((Status::BadRequest, ErrorResponse::new("missing case id")))
+        };
+        let case = match case_database.read_case_metadata(&case_id).await {
+            Ok(Some(case)) => case,
+            Ok(None) => return Outcome::Error((Status::NotFound, ErrorResponse::new("no case found"))),
+            Err(e) => {
+                eprintln!("error reading case by id: {}", e);
+                return Outcome::Error((Status::InternalServerError, ErrorResponse{error: "internal_server_error".into()}));
+            }
+        };
+
+        if !user.is_member(&case.group_id) {
+            return Outcome::Error((Status::Forbidden, ErrorResponse::new("not a member of the group of the case")));
+        }
+        let role = match role_database.read_role(&case.case_id, &user.user_id).await {
+            Ok(role) => role,
+            Err(e) => {
+                eprintln!("error reading role: {}", e);
+                return Outcome::Error((Status::InternalServerError, ErrorResponse{error: "internal_server_error".into()}));
+            }
+        };
+        Outcome::Success(AuthorizeCaseGuard { user, case, role })
+    }
+
+    // fails with 403 unless the user has at least the role on the case.
+    pub fn require(&self, role: Role) -> Result<(), Custom<Json<ErrorResponse>>> {
+        match self.role >= role {
+            true => Ok(()),
+            false => Err(Custom(Status::Forbidden, Json(ErrorResponse{error: format!("this requires the {} role on the case", role.name())})))
+        }
+    }
+
+    // the value of the case_id segment of the route the request matched.
+    fn case_id(request: &Request<'_>) -> Option<String> {
+        let route = request.route()?;
+        let index = route.uri.path()
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .position(|segment| segment == "<case_id>")?;
+        request.routed_segment(index).map(|v| v.to_string())
+    }
+}
+
+// verifies a websocket client the way AuthorizeCaseGuard verifies other clients. a websocket can't carry headers,
+// and session tokens would end up in logs if passed in the url, so the client connects with a single-use ticket
+// for the case as the `ticket` query value instead. the user the ticket was issued to is the identity of the connection.
+pub struct AuthorizeSocketGuard {
+    pub user: User,
+    pub case: CaseMetadata,
+    pub role: Role
+}
+#[async_trait]
+impl<'r> FromRequest<'r> for AuthorizeSocketGuard {
+    type Error = ErrorResponse;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let ticket_service = match request.guard::<&State<TicketService>>().await {
+            Outcome::Success(ticket_service) => ticket_service,
+            Outcome::Error(_) | Outcome::Forward(_) => {
+                return Outcome::Error((Status::InternalServerError, ErrorResponse{error: "internal_server_error".into()}));
+            }
+        };
+        let ticket = match request.query_value::<String>("ticket") {
+            Some(Ok(ticket)) => ticket,
+            _ => return Outcome::Error((Status::BadRequest, ErrorResponse {
+                error: "missing ticket".into()
+            }))
+        };
+        let case_id = match AuthorizeCaseGuard::case_id(request) {
+            Some(v) => v,
+            None => return Outcome::Error((Status::BadRequest, ErrorResponse::new("missing case id")))
+        };
+
+        let user = match ticket_service.redeem(&ticket, &case_id).await {
+            Some(user) => user,
+            None => return Outcome::Error((Status::Unauthorized, ErrorResponse {
+                error: "invalid ticket".into()
+            }))
+        };
+        match AuthorizeCaseGuard::authorize(request, user).await {
+            Outcome::Success(AuthorizeCaseGuard { user, case, role }) => Outcome::Success(AuthorizeSocketGuard { user, case, role }),
+            Outcome::Error(e) => Outcome::Error(e),
+            Outcome::Forward(status) => Outcome::Forward(status)
+        }
+    }
+}
 
 
 
@@ -1,6 +1,10 @@
 
+pub mod action_handler;
 pub mod case_handler;
 pub mod collaboration_handler;
+pub mod comment_handler;
+pub mod role_handler;
+pub mod template_handler;
 
 pub mod middleware_handler;
 pub mod cors;
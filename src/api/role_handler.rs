@@ -0,0 +1,68 @@
+use rocket::serde::json::Json;
+use rocket::State;
+use std::sync::Arc;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+
+use crate::types::ErrorResponse;
+use crate::types::role_database::{Role, RoleAssignment};
+use crate::types::role_handler::RoleBody;
+use crate::database::role::RoleDatabase;
+use crate::service::user::UserService;
+use crate::api::middleware_handler::AuthorizeCaseGuard;
+
+
+// the roles given out on the case. members of the case's group without a role are viewers, or editors until the case has an owner.
+#[get("/api/case/<case_id>/role/list")]
+pub async fn get_roles(_guard: AuthorizeCaseGuard, role_database: &State<RoleDatabase>, case_id: String) -> Result<Custom<Json<Vec<RoleAssignment>>>, Custom<Json<ErrorResponse>>> {
+    match role_database.read_roles(&case_id).await {
+        Ok(roles) => Ok(Custom(Status::Ok, Json(roles))),
+        Err(e) => {
+            eprintln!("error reading roles: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading roles"))))
+        }
+    }
+}
+
+// gives a user a role on the case. group memberships are only known from the tokens of the users themselves, so the
+// user isn't checked to be in the case's group, a role only takes effect for members of it. owners can't change their
+// own role, so a case can't lose its last owner that way. admins in the case's group can give out roles as well,
+// e.g. to give an owner to a case from before roles.
+#[put("/api/case/<case_id>/role/<user_id>", data = "<data>")]
+pub async fn set_role(guard: AuthorizeCaseGuard, role_database: &State<RoleDatabase>, user_service: &State<Arc<UserService>>, case_id: String, user_id: String, data: Json<RoleBody>) -> Result<Custom<Json<RoleAssignment>>, Custom<Json<ErrorResponse>>> {
+    if !user_service.is_admin(&guard.user) {
+        guard.require(Role::Owner)?;
+    }
+    if guard.role == Role::Owner && user_id == guard.user.user_id {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("owners can't change their own role"))));
+    }
+
+    let assignment = RoleAssignment { case_id, group_id: guard.case.group_id, user_id, role: data.role };
+    match role_database.set_role(&assignment).await {
+        Ok(()) => Ok(Custom(Status::Ok, Json(assignment))),
+        Err(e) => {
+            eprintln!("error setting role: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error setting role"))))
+        }
+    }
+}
+
+// takes a user's role on the case, the same way set_role gives it.
+#[delete("/api/case/<case_id>/role/<user_id>")]
+pub async fn delete_role(guard: AuthorizeCaseGuard, role_database: &State<RoleDatabase>, user_service: &State<Arc<UserService>>, case_id: String, user_id: String) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+    if !user_service.is_admin(&guard.user) {
+        guard.require(Role::Owner)?;
+    }
+    if guard.role == Role::Owner && user_id == guard.user.user_id {
+        return Err(Custom(Status::BadRequest, Json(ErrorResponse::new("owners can't change their own role"))));
+    }
+
+    match role_database.delete_role(&case_id, &user_id).await {
+        Ok(true) => Ok(Custom(Status::Ok, "successfully removed role".into())),
+        Ok(false) => Err(Custom(Status::NotFound, Json(ErrorResponse::new("the user has no role on the case")))),
+        Err(e) => {
+            eprintln!("error removing role: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error removing role"))))
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+
+use mongodb::bson::Document;
+
+use crate::types::ErrorResponse;
+use crate::types::template_database::TemplateMetadata;
+use crate::database::case::CaseDatabase;
+use crate::database::template::TemplateDatabase;
+use crate::framework::{Framework, FrameworkRegistry};
+use crate::api::middleware_handler::{AuthorizeAdminGuard, AuthorizeClientGuard};
+
+
+#[get("/api/template/list?<framework>")]
+pub async fn get_templates(_guard: AuthorizeClientGuard, template_database: &State<TemplateDatabase>, framework: Option<String>) -> Result<Custom<Json<Vec<TemplateMetadata>>>, Custom<Json<ErrorResponse>>> {
+    match template_database.read_templates(framework.as_ref()).await {
+        Ok(templates) => Ok(Custom(Status::Ok, Json(templates))),
+        Err(e) => {
+            eprintln!("error reading templates: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading templates"))))
+        }
+    }
+}
+
+#[get("/api/template/<framework>/<version>/<locale>")]
+pub async fn get_template(_guard: AuthorizeClientGuard, template_database: &State<TemplateDatabase>, framework: &str, version: i32, locale: String) -> Result<Custom<Json<Document>>, Custom<Json<ErrorResponse>>> {
+    match template_database.read_template(framework, Some(version), Some(&locale)).await {
+        Ok(Some(template)) => Ok(Custom(Status::Ok, Json(template))),
+        Ok(None) => Err(Custom(Status::NotFound, Json(ErrorResponse::new("no template found")))),
+        Err(e) => {
+            eprintln!("error reading template: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error reading template"))))
+        }
+    }
+}
+
+// uploads a new version of a framework's template for a locale, the template is checked against the framework's case structure.
+// templates are shared by all groups, so only admins change them.
+#[post("/api/template/<framework>/<version>/<locale>", data = "<data>")]
+pub async fn create_template(
+    _guard: AuthorizeAdminGuard,
+    template_database: &State<TemplateDatabase>,
+    frameworks: &State<FrameworkRegistry>,
+    framework: &str,
+    version: i32,
+    locale: String,
+    data: Json<serde_json::Value>
+) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+    let framework = find_framework(frameworks, framework)?;
+    let template = parse_template(framework, data.into_inner())?;
+
+    match template_database.create_template(framework.id(), version, &locale, template).await {
+        Ok(true) => Ok(Custom(Status::Created, "successfully created template".into())),
+        Ok(false) => Err(Custom(Status::Conflict, Json(ErrorResponse{error: format!("version {} of the {} template already exists for {}", version, framework.id(), locale)}))),
+        Err(e) => {
+            eprintln!("error creating template: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error creating template"))))
+        }
+    }
+}
+
+#[put("/api/template/<framework>/<version>/<locale>", data = "<data>")]
+pub async fn update_template(
+    _guard: AuthorizeAdminGuard,
+    template_database: &State<TemplateDatabase>,
+    frameworks: &State<FrameworkRegistry>,
+    framework: &str,
+    version: i32,
+    locale: String,
+    data: Json<serde_json::Value>
+) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+    let framework = find_framework(frameworks, framework)?;
+    let template = parse_template(framework, data.into_inner())?;
+
+    match template_database.replace_template(framework.id(), version, &locale, template).await {
+        Ok(true) => Ok(Custom(Status::Ok, "successfully updated template".into())),
+        Ok(false) => Err(Custom(Status::NotFound, Json(ErrorResponse::new("no template found")))),
+        Err(e) => {
+            eprintln!("error updating template: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error updating template"))))
+        }
+    }
+}
+
+// deletes a version of a template no case follows, as cases read the values of their fields from their template
+// and are migrated from it.
+#[delete("/api/template/<framework>/<version>/<locale>")]
+pub async fn delete_template(_guard: AuthorizeAdminGuard, template_database: &State<TemplateDatabase>, case_database: &State<CaseDatabase>, framework: &str, version: i32, locale: String) -> Result<Custom<String>, Custom<Json<ErrorResponse>>> {
+    match case_database.uses_template(framework, version, &locale).await {
+        Ok(false) => (),
+        Ok(true) => return Err(Custom(Status::Conflict, Json(ErrorResponse{error: format!("cases still follow version {} of the {} template for {}", version, framework, locale)}))),
+        Err(e) => {
+            eprintln!("error reading cases by template: {}", e);
+            return Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error deleting template"))))
+        }
+    }
+    match template_database.delete_template(framework, version, &locale).await {
+        Ok(true) => Ok(Custom(Status::Ok, "successfully deleted template".into())),
+        Ok(false) => Err(Custom(Status::NotFound, Json(ErrorResponse::new("no template found")))),
+        Err(e) => {
+            eprintln!("error deleting template: {}", e);
+            Err(Custom(Status::InternalServerError, Json(ErrorResponse::new("error deleting template"))))
+        }
+    }
+}
+
+fn find_framework<'a>(frameworks: &'a FrameworkRegistry, framework: &str) -> Result<&'a dyn Framework, Custom<Json<ErrorResponse>>> {
+    match frameworks.get(framework) {
+        Some(v) => Ok(v),
+        None => Err(Custom(Status::NotFound, Json(ErrorResponse{error: format!("no framework {}", framework)})))
+    }
+}
+
+fn parse_template(framework: &dyn Framework, template: serde_json::Value) -> Result<Document, Custom<Json<ErrorResponse>>> {
+    framework.parse_template(template).map_err(|e| Custom(Status::BadRequest, Json(ErrorResponse{error: e})))
+}
@@ -0,0 +1,71 @@
+use mongodb::{bson::{self, doc, Document}, error::Error, options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument}, Collection, Database, IndexModel};
+use rocket::futures::TryStreamExt;
+
+use crate::types::action_database::{ActionFilter, ActionItem};
+
+
+pub struct ActionDatabase {
+    actions: Collection<ActionItem>
+}
+
+impl ActionDatabase {
+
+    pub async fn new(database: &Database) -> Self {
+        let actions = database.collection::<ActionItem>("actions");
+
+        // action items are read per case and across the cases of a group
+        for keys in [doc! { "case_id": 1 }, doc! { "group_id": 1, "status": 1 }] {
+            let index = IndexModel::builder().keys(keys).build();
+            actions.create_index(index, None).await.unwrap();
+        }
+
+        Self {
+            actions
+        }
+    }
+
+    pub async fn create_action(&self, action: &ActionItem) -> Result<(), Error> {
+        self.actions.insert_one(action, None).await?;
+        Ok(())
+    }
+
+    // reads the action items matching the filter, oldest first.
+    pub async fn read_actions(&self, filter: &ActionFilter) -> Result<Vec<ActionItem>, Error> {
+        let mut query = doc! {};
+        if let Some(case_id) = &filter.case_id {
+            query.insert("case_id", case_id);
+        }
+        if let Some(group_id) = &filter.group_id {
+            query.insert("group_id", group_id);
+        }
+        if let Some(status) = &filter.status {
+            query.insert("status", bson::to_bson(status)?);
+        }
+        if let Some(owner) = &filter.owner {
+            query.insert("owner", owner);
+        }
+        let options = FindOptions::builder().sort(doc! { "created_at": 1 }).build();
+        let cursor = self.actions.find(query, options).await?;
+        cursor.try_collect().await
+    }
+
+    // sets the fields of an action item of the case, returning the action item as it is afterwards.
+    pub async fn update_action(&self, case_id: &String, action_id: &String, fields: Document) -> Result<Option<ActionItem>, Error> {
+        let filter = doc! { "case_id": case_id, "action_id": action_id };
+        let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+        self.actions.find_one_and_update(filter, doc! { "$set": fields }, options).await
+    }
+
+    // deletes an action item of the case, returning what it was.
+    pub async fn delete_action(&self, case_id: &String, action_id: &String) -> Result<Option<ActionItem>, Error> {
+        let filter = doc! { "case_id": case_id, "action_id": action_id };
+        self.actions.find_one_and_delete(filter, None).await
+    }
+
+    // deletes every action item of the case, used when the case itself is deleted.
+    pub async fn delete_actions_by_case(&self, case_id: &String) -> Result<(), Error> {
+        self.actions.delete_many(doc! { "case_id": case_id }, None).await?;
+        Ok(())
+    }
+
+}
@@ -0,0 +1,101 @@
+use mongodb::{bson::{doc, Bson, Document}, error::Error, options::FindOptions, Collection, Database, IndexModel};
+use rocket::futures::TryStreamExt;
+
+use crate::types::audit_database::{AuditAction, AuditFilter, AuditHistory, AuditRecord, HistoryPoint};
+use crate::types::case_database::ChangeRecord;
+
+
+pub struct AuditDatabase {
+    audit: Collection<AuditRecord>
+}
+
+impl AuditDatabase {
+
+    pub async fn new(database: &Database) -> Self {
+        let audit = database.collection::<AuditRecord>("audit");
+
+        let index = IndexModel::builder()
+            .keys(doc! { "case_id": 1, "timestamp": -1 })
+            .build();
+        audit.create_index(index, None).await.unwrap();
+
+        Self {
+            audit
+        }
+    }
+
+    pub async fn create_record(&self, record: &AuditRecord) -> Result<(), Error> {
+        self.audit.insert_one(record, None).await?;
+        Ok(())
+    }
+
+    // records a change applied to a case field, along with the value it replaced.
+    // the item is recorded through its first two ids, e.g. a cis18 control and safeguard or just a nis2 measure.
+    pub async fn create_change_record(&self, action: AuditAction, change: &ChangeRecord, old_value: Bson, new_value: Bson) -> Result<(), Error> {
+        let target = change.event.change();
+        let record = AuditRecord {
+            case_id: change.case_id.to_string(),
+            user_id: change.user_id.to_string(),
+            action,
+            path: Some(change.path.to_string()),
+            control_id: target.and_then(|change| change.ids.first().cloned()),
+            subcontrol_id: target.and_then(|change| change.ids.get(1).cloned()),
+            field: target.map(|change| change.field.to_string()),
+            old_value: Some(old_value),
+            new_value: Some(new_value),
+            revision: Some(change.revision),
+            timestamp: change.timestamp
+        };
+        self.create_record(&record).await
+    }
+
+    // reads the field changes made to a case after a point in its history, oldest first.
+    // optionally limited to a single field.
+    pub async fn read_changes_after(&self, case_id: &String, point: &HistoryPoint, path: Option<&String>) -> Result<Vec<AuditRecord>, Error> {
+        let mut query = doc! { "case_id": case_id, "action": { "$in": ["change", "revert"] } };
+        match point {
+            HistoryPoint::Revision(revision) => query.insert("revision", doc! { "$gt": revision }),
+            HistoryPoint::Timestamp(timestamp) => query.insert("timestamp", doc! { "$gt": timestamp })
+        };
+        if let Some(path) = path {
+            query.insert("path", path);
+        }
+        let options = FindOptions::builder().sort(doc! { "revision": 1 }).build();
+        let cursor = self.audit.find(query, options).await?;
+        cursor.try_collect().await
+    }
+
+    // reads a page of a case's history, newest first.
+    pub async fn read_history(&self, case_id: &String, filter: &AuditFilter, page: u64, page_size: i64) -> Result<AuditHistory, Error> {
+        let mut query = doc! { "case_id": case_id };
+        if let Some(user_id) = &filter.user_id {
+            query.insert("user_id", user_id);
+        }
+        if let Some(control_id) = &filter.control_id {
+            query.insert("control_id", control_id);
+        }
+        let mut timestamp = Document::new();
+        if let Some(from) = filter.from {
+            timestamp.insert("$gte", from);
+        }
+        if let Some(to) = filter.to {
+            timestamp.insert("$lte", to);
+        }
+        if !timestamp.is_empty() {
+            query.insert("timestamp", timestamp);
+        }
+
+        let total = self.audit.count_documents(query.clone(), None).await?;
+
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": -1 })
+            .skip(page * page_size as u64)
+            .limit(page_size)
+            .build();
+        let cursor = self.audit.find(query, options).await?;
+        let records = cursor.try_collect().await?;
+
+        Ok(AuditHistory { records, page, page_size, total })
+    }
+
+}
@@ -1,84 +1,124 @@
-use mongodb::{bson::{self, doc}, error::Error, options::{ClientOptions, FindOptions, UpdateOptions}, Client, Collection};
+use mongodb::{bson::{self, doc, Bson, Document}, error::{Error, ErrorKind, WriteFailure}, options::{FindOneOptions, FindOptions, IndexOptions, UpdateOptions}, Collection, Database, IndexModel};
 use rocket::futures::TryStreamExt;
-use std::{collections::HashMap, env};
+use rocket::tokio::time::sleep;
+use std::collections::HashMap;
+use std::time::Duration;
+use chrono::Utc;
 use uuid::Uuid;
 
-use crate::types::case_database::{CIS18Case, Case, CaseMetadata, GroupCases};
-use crate::types::collaboration_handler::{Message, Change, TextOrIntValue};
+use crate::framework::{self, Framework};
+use crate::types::case_database::{CaseDocument, CaseMetadata, CaseRevision, ChangeOutcome, ChangeRecord, GroupCases};
+use crate::types::framework::{CaseOptions, FieldLocation};
+use crate::types::collaboration_handler::{Message, Event, TextOrIntValue, MAX_TEXT_LENGTH};
+use crate::service::text;
 
 
+// how many times a change is retried when other changes keep landing on the case in between.
+const MAX_CHANGE_ATTEMPTS: usize = 5;
+
+// the most changes a text edit is transformed against before the client is asked to resync instead.
+const MAX_REBASE_CHANGES: i64 = 500;
+
+// how long a revision claimed in the change log may go without the case moving to it, before the claim is
+// considered abandoned, e.g. by an instance that went down in between.
+const CLAIM_TIMEOUT_MS: i64 = 30000;
+
 pub struct CaseDatabase {
-    cases: Collection<Case>,
+    case_documents: Collection<Document>,
     cases_metadata: Collection<CaseMetadata>,
-    cis18_template: Collection<CIS18Case>
+    cases_revision: Collection<CaseRevision>,
+    changes: Collection<ChangeRecord>
 }
 
 impl CaseDatabase {
 
-    pub async fn new() -> Self {
-        let client_uri = env::var("MONGODB_CONNECTION_STRING").unwrap();
-        let database_name = "core";
-
-        let client_options = ClientOptions::parse(client_uri).await.unwrap();
-        let client = Client::with_options(client_options).unwrap();
-        let database = client.database(database_name);
-
-        let cases = database.collection::<Case>("cases");
+    pub async fn new(database: &Database) -> Self {
+        let case_documents = database.collection::<Document>("cases");
         let cases_metadata = database.collection::<CaseMetadata>("cases");
-        let cis18_template = database.collection::<CIS18Case>("templates");
+        let cases_revision = database.collection::<CaseRevision>("cases");
+        let changes = database.collection::<ChangeRecord>("changes");
+
+        // a revision can only be produced once per case
+        let index = IndexModel::builder()
+            .keys(doc! { "case_id": 1, "revision": 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        changes.create_index(index, None).await.unwrap();
 
         Self {
-            cases,
+            case_documents,
             cases_metadata,
-            cis18_template
+            cases_revision,
+            changes
         }
     }
 
-    pub async fn create_cis18_case(&self, group_id: &String, name: &String, implementation_group: i32) -> Result<Option<String>, Box<dyn std::error::Error>> {
-        let filter = doc! { "framework": "cis18" };
-        let result = self.cis18_template.find_one(filter, None).await?;
-        let mut template = match result {
-            Some(v) => v,
-            None => return Ok(None)
-        };
+    // creates a case from a template of the framework, remembering which version of the template it was made from.
+    pub async fn create_case(&self, framework: &dyn Framework, mut template: Document, group_id: &String, name: &String, options: &CaseOptions) -> Result<String, Box<dyn std::error::Error>> {
+        let version = template.remove("version");
+        let locale = template.remove("locale");
+        template.remove("updated_at");
+        template.remove("_id");
 
         let case_id = Uuid::new_v4().to_string();
-        template.case_id = case_id.clone();
-        template.group_id = group_id.to_string();
-        template.name = name.to_string();
-        template.framework = "cis18".to_owned();
-        template.implementation_group = implementation_group;
+        let mut case = template;
+        case.insert("case_id", &case_id);
+        case.insert("group_id", group_id);
+        case.insert("name", name);
+        case.insert("framework", framework.id());
+        case.insert("revision", 0_i64);
+        case.insert("template_version", version.unwrap_or(Bson::Null));
+        case.insert("template_locale", locale.unwrap_or(Bson::Null));
+        framework.prepare_case(&mut case, options)?;
 
-        self.cases.insert_one(Case::CIS18(template), None).await?;
-        Ok(Some(case_id))
+        // the template has to make up a valid case of the framework
+        let case = framework.parse_case(case)?;
+
+        self.case_documents.insert_one(case, None).await?;
+        Ok(case_id)
     }
 
-    pub async fn read_case_by_id(&self, case_id: String) -> Result<Option<Case>, Error> {
-        let filter = doc! { "case_id": case_id }; 
-        let result = self.cases.find_one(filter, None).await?;
-        Ok(result)
+    // whether any case was created from or migrated to a version of a framework's template.
+    pub async fn uses_template(&self, framework: &str, version: i32, locale: &String) -> Result<bool, Error> {
+        let filter = doc! { "framework": framework, "template_version": version, "template_locale": locale };
+        Ok(self.case_documents.find_one(filter, None).await?.is_some())
     }
 
-    pub async fn rename_case(&self, case_id: &String, name: &String) -> Result<Option<()>, Error> {
+    // the case as it is sent to clients, which is how it is stored without the database's own id.
+    pub async fn read_case_by_id(&self, case_id: String) -> Result<Option<Document>, Error> {
+        let filter = doc! { "case_id": case_id };
+        let options = FindOneOptions::builder()
+            .projection(doc! { "_id": 0 })
+            .build();
+        self.case_documents.find_one(filter, options).await
+    }
+
+    // the case as it is stored, for reading it through field locations.
+    pub async fn read_case_document(&self, case_id: &String) -> Result<Option<Document>, Error> {
+        let filter = doc! { "case_id": case_id };
+        self.case_documents.find_one(filter, None).await
+    }
+
+    // renames the case, returning its previous name.
+    pub async fn rename_case(&self, case_id: &String, name: &String) -> Result<Option<String>, Error> {
         let filter = doc! { "case_id": case_id };
         let change = doc! { "$set": { "name": name} };
-        let options = UpdateOptions::builder().upsert(false).build();
-        self.cases.update_one(filter, change, options).await?;
-        Ok(Some(()))
+        let result = self.cases_metadata.find_one_and_update(filter, change, None).await?;
+        Ok(result.map(|case| case.name))
     }
 
-    pub async fn delete_case(&self, case_id: &String) -> Result<Option<()>, Error> {
+    // deletes the case, returning what it was.
+    pub async fn delete_case(&self, case_id: &String) -> Result<Option<CaseMetadata>, Error> {
         let query = doc! { "case_id": case_id };
-        self.cases.delete_one(query, None).await?;
-        Ok(Some(()))
+        self.cases_metadata.find_one_and_delete(query, None).await
     }
 
-    pub async fn read_cases_by_group_id(&self, group_id: String) -> Result<Vec<Case>, Error> {
+    pub async fn read_cases_by_group_id(&self, group_id: String) -> Result<Vec<CaseMetadata>, Error> {
         let filter = doc! { "group_id": group_id };
         let options = FindOptions::builder()
             .projection(doc! { "case_id": 1, "group_id": 1, "name": 1, "framework": 1, "implementation_group": 1 })
             .build();
-        let mut cursor = self.cases.find(filter, options).await?;
+        let mut cursor = self.cases_metadata.find(filter, options).await?;
         let mut cases = Vec::new();
         while let Some(case) = cursor.try_next().await? {
             println!("{:?}", case);
@@ -107,49 +147,294 @@ impl CaseDatabase {
         Ok(group_cases)
     }
 
+    pub async fn read_case_metadata(&self, case_id: &String) -> Result<Option<CaseMetadata>, Error> {
+        let filter = doc! { "case_id": case_id };
+        self.cases_metadata.find_one(filter, None).await
+    }
+
     pub async fn read_case_framework(&self, case_id: &String) -> Result<String, Box<dyn std::error::Error>> {
         let filter = doc! { "case_id": case_id.to_string() };
-        let result = self.cases.find_one(filter, None).await?;
+        let result = self.cases_metadata.find_one(filter, None).await?;
         match result {
-            Some(Case::CIS18(case)) => Ok(case.framework),
-            Some(Case::NIS2(case)) => Ok(case.framework),
+            Some(case) => Ok(case.framework),
             None => Err("no case found".into())
         }
     }
 
-    pub async fn update_cis18_content(&self, case_id: &String, message: &Message) -> Result<(), Box<dyn  std::error::Error>> {
-        if let Change::CIS18Change(ref change) = message.data {
-            let filter = doc! {
+    pub async fn read_case_revision(&self, case_id: &String) -> Result<Option<i64>, Error> {
+        let filter = doc! { "case_id": case_id };
+        let options = FindOneOptions::builder()
+            .projection(doc! { "revision": 1 })
+            .build();
+        let result = self.cases_revision.find_one(filter, options).await?;
+        Ok(result.map(|v| v.revision))
+    }
+
+    // replaces parts of a case that aren't edited through changes, as long as the case is still at the given revision.
+    // the new revision is claimed in the change log like any change, marked as a restructure clients have to resync from.
+    // returns the revision the case is at afterwards, None if another change landed first.
+    pub async fn update_case_structure(&self, case_id: &String, revision: i64, user_id: &String, reason: &str, mut fields: Document) -> Result<Option<i64>, Error> {
+        let record = ChangeRecord {
+            case_id: case_id.to_string(),
+            revision: revision + 1,
+            user_id: user_id.to_string(),
+            path: fields.keys().cloned().collect::<Vec<_>>().join(","),
+            event: Event::Restructure(reason.to_string()),
+            timestamp: Utc::now().timestamp_millis()
+        };
+        let filter = doc! { "case_id": case_id, "revision": current_revision(revision) };
+        fields.insert("revision", revision + 1);
+        match self.commit(&record, filter, doc! { "$set": fields }, None).await? {
+            true => Ok(Some(revision + 1)),
+            false => Ok(None)
+        }
+    }
+
+    // reads the changes made after the given revision up to the latest one, in the order they were applied. optionally
+    // only those made to a field. changes past the latest revision are still being applied, and may yet be taken back.
+    pub async fn read_changes_since(&self, case_id: &String, revision: i64, latest: i64, path: Option<&String>) -> Result<Vec<ChangeRecord>, Error> {
+        let mut filter = doc! { "case_id": case_id, "revision": { "$gt": revision, "$lte": latest } };
+        if let Some(path) = path {
+            filter.insert("path", path);
+        }
+        let options = FindOptions::builder().sort(doc! { "revision": 1 }).build();
+        let cursor = self.changes.find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    // deletes the change log of the case, used when the case itself is deleted.
+    pub async fn delete_changes_by_case(&self, case_id: &String) -> Result<(), Error> {
+        self.changes.delete_many(doc! { "case_id": case_id }, None).await?;
+        Ok(())
+    }
+
+    // reads the revision of a case along with the current value of a field, and the case itself.
+    // returns None if an item on the way to the field doesn't exist, and a null value if the item has no such field.
+    async fn read_field(&self, case_id: &String, location: &FieldLocation) -> Result<Option<(i64, Bson, Document)>, Box<dyn std::error::Error + Send + Sync>> {
+        let case = match self.read_case_document(case_id).await? {
+            Some(v) => v,
+            None => return Err("no case found".into())
+        };
+        Ok(location.read(&case).map(|value| (case.revision(), value, case)))
+    }
+
+    // applies a change made against a given revision of the case.
+    // a change made against an older revision is rebased onto the latest one. a text edit is transformed against the
+    // text edits made to the field since, any other change is rejected if the same field has changed since.
+    // an applied change bumps the case revision and is stored in the change log.
+    pub async fn update_content(&self, framework: &dyn Framework, case_id: &String, user_id: &String, message: &Message) -> Result<ChangeOutcome, Box<dyn std::error::Error + Send + Sync>> {
+        let change = match message.event.change() {
+            Some(v) => v,
+            None => return Err("message carries no change".into())
+        };
+
+        // only whitelisted fields with valid values can be changed
+        let location = match framework.validate(change) {
+            Ok(v) => v,
+            Err(reason) => match self.read_case_revision(case_id).await? {
+                Some(revision) => return Ok(ChangeOutcome::Rejected { revision, reason }),
+                None => return Err("no case found".into())
+            }
+        };
+        let path = location.path();
+
+        let base_revision = match message.revision {
+            Some(v) => v,
+            None => match self.read_case_revision(case_id).await? {
+                Some(revision) => return Ok(ChangeOutcome::Rejected { revision, reason: "missing revision".into() }),
+                None => return Err("no case found".into())
+            }
+        };
+
+        for attempt in 0..MAX_CHANGE_ATTEMPTS {
+            let (revision, old_value, case) = match self.read_field(case_id, &location).await? {
+                Some(v) => v,
+                None => return Ok(ChangeOutcome::Rejected { revision: base_revision, reason: format!("{} is not in the case", path) })
+            };
+            // the values a field can take are listed by the case's template
+            if let Some(Err(reason)) = change.value.as_ref().map(|value| framework::check_allowed(&case, &location.field, value)) {
+                return Ok(ChangeOutcome::Rejected { revision, reason });
+            }
+            if base_revision > revision {
+                return Ok(ChangeOutcome::Stale { revision, reason: format!("revision {} does not exist yet", base_revision) });
+            }
+
+            let (new_value, event) = match (change.operations.as_ref(), change.value.as_ref()) {
+                (Some(operations), _) => {
+                    let text = match &old_value {
+                        Bson::String(v) => v,
+                        _ => return Ok(ChangeOutcome::Rejected { revision, reason: format!("no text field {}", path) })
+                    };
+
+                    // bring the operations up to date with the text edits made since the client's revision. every
+                    // revision in between has to be in the log, a missing one could have been an edit to the same text
+                    if revision - base_revision > MAX_REBASE_CHANGES {
+                        return Ok(ChangeOutcome::Stale { revision, reason: format!("too many changes since revision {}", base_revision) });
+                    }
+                    let changes = self.read_changes_since(case_id, base_revision, revision, None).await?;
+                    let complete = changes.len() as i64 == revision - base_revision && changes.iter()
+                        .enumerate()
+                        .all(|(i, record)| record.revision == base_revision + 1 + i as i64);
+                    if !complete {
+                        return Ok(ChangeOutcome::Stale { revision, reason: format!("the changes since revision {} are not all known", base_revision) });
+                    }
+
+                    let mut applied = Vec::new();
+                    for record in changes {
+                        let prior = match (&record.event, record.path == path) {
+                            (Event::Restructure(_), _) => return Ok(ChangeOutcome::Stale { revision, reason: format!("the case was restructured since revision {}", base_revision) }),
+                            (_, false) => continue,
+                            (event, true) => event.change().and_then(|prior| prior.operations.as_ref())
+                        };
+                        match prior {
+                            Some(prior) => applied.extend(prior.iter().cloned()),
+                            None => return Ok(ChangeOutcome::Stale { revision, reason: format!("{} was replaced since revision {}", path, base_revision) })
+                        }
+                    }
+                    // the operations are checked against the text the client edited before anything is done with them
+                    let length = match text::length_before(text, &applied) {
+                        Some(v) => v,
+                        None => return Ok(ChangeOutcome::Stale { revision, reason: format!("the changes to {} since revision {} don't add up", path, base_revision) })
+                    };
+                    if let Err(reason) = text::check(length, operations) {
+                        return Ok(ChangeOutcome::Rejected { revision, reason });
+                    }
+                    let operations = match text::transform(operations, &applied) {
+                        Some(v) => v,
+                        None => return Ok(ChangeOutcome::Rejected { revision, reason: "edit is outside of the text".into() })
+                    };
+
+                    let text = match text::apply(text, &operations) {
+                        Ok(v) => v,
+                        Err(reason) => return Ok(ChangeOutcome::Stale { revision, reason })
+                    };
+                    if text.chars().count() > MAX_TEXT_LENGTH {
+                        return Ok(ChangeOutcome::Rejected { revision, reason: format!("{} can be at most {} characters", location.field, MAX_TEXT_LENGTH) });
+                    }
+                    (Bson::String(text), Event::TextChange(change.with_operations(operations)))
+                },
+                (None, Some(value)) => {
+                    if base_revision < revision && !self.read_changes_since(case_id, base_revision, revision, Some(&path)).await?.is_empty() {
+                        return Ok(ChangeOutcome::Stale { revision, reason: format!("{} has changed since revision {}", path, base_revision) });
+                    }
+                    let value = match value {
+                        TextOrIntValue::String(val) => bson::to_bson(val)?,
+                        TextOrIntValue::Number(val) => bson::to_bson(val)?,
+                        TextOrIntValue::Documentation(val) => bson::to_bson(val)?,
+                    };
+                    (value, message.event.clone())
+                },
+                (None, None) => return Err("change has neither a value nor operations".into())
+            };
+
+            let mut filter = doc! {
                 "case_id": &case_id,
-                "controls.id": &change.control_id,
-                "controls.subcontrols.id": &change.subcontrol_id,
+                "revision": current_revision(revision)
             };
+            filter.extend(location.filter());
 
             let update = doc! {
                 "$set": {
-                    format!("controls.$.subcontrols.$[elem].{}", &change.field): match &change.value {
-                        TextOrIntValue::String(val) => bson::to_bson(val)?,
-                        TextOrIntValue::Number(val) => bson::to_bson(val)?,
-                    }
+                    location.update_key(): new_value.clone(),
+                    "revision": revision + 1
                 }
             };
 
-            let array_filters = vec![
-                doc! { "elem.id": &change.subcontrol_id }
-            ];
-
             let update_options = UpdateOptions::builder()
-                .array_filters(array_filters)
+                .array_filters(location.array_filters())
                 .build();
 
-            self.cases.update_one(filter, update, update_options).await?;
+            let record = ChangeRecord {
+                case_id: case_id.to_string(),
+                revision: revision + 1,
+                user_id: user_id.to_string(),
+                path: path.clone(),
+                event,
+                timestamp: Utc::now().timestamp_millis()
+            };
+            if self.commit(&record, filter, update, update_options).await? {
+                return Ok(ChangeOutcome::Applied { record, old_value, new_value });
+            }
+
+            // another change landed first, give it a moment to finish
+            println!("case {} changed during update, retrying", case_id);
+            sleep(Duration::from_millis(10 * (attempt as u64 + 1))).await;
+        }
+
+        let revision = self.read_case_revision(case_id).await?.unwrap_or(base_revision);
+        Ok(ChangeOutcome::Rejected { revision, reason: "case is too busy, try again".into() })
+    }
+
+    // moves the case to the revision of a change, as long as the case still matches the filter.
+    // the revision is claimed first by storing the change in the log, whose unique index lets only one writer claim it,
+    // so the log holds every change up to the revision a case is seen at. the claim is taken back if the case doesn't
+    // move, returning false, in which case the change can be retried on top of whatever landed in between.
+    async fn commit(&self, record: &ChangeRecord, filter: Document, update: Document, options: impl Into<Option<UpdateOptions>>) -> Result<bool, Error> {
+        if let Err(e) = self.changes.insert_one(record, None).await {
+            return match is_duplicate(&e) {
+                true => {
+                    self.release_abandoned_claim(&record.case_id, record.revision).await?;
+                    Ok(false)
+                },
+                false => Err(e)
+            };
+        }
+
+        match self.case_documents.update_one(filter, update, options).await {
+            Ok(result) if result.matched_count == 1 => Ok(true),
+            Ok(_) => {
+                self.release_claim(record).await?;
+                Ok(false)
+            },
+            Err(e) => {
+                // the update may have gone through all the same, only the writer holding the claim can have moved the case to it
+                match self.read_case_revision(&record.case_id).await {
+                    Ok(Some(revision)) if revision == record.revision => Ok(true),
+                    Ok(_) => {
+                        self.release_claim(record).await?;
+                        Err(e)
+                    },
+                    // the claim is left to be released once it is abandoned
+                    Err(_) => Err(e)
+                }
+            }
+        }
+    }
 
-            Ok(())
-        } else {
-            Err("Invalid change type for CIS18".into())
+    async fn release_claim(&self, record: &ChangeRecord) -> Result<(), Error> {
+        let filter = doc! { "case_id": &record.case_id, "revision": record.revision, "timestamp": record.timestamp };
+        self.changes.delete_one(filter, None).await?;
+        Ok(())
+    }
+
+    // releases a claim on a revision the case never moved to, if it was made long enough ago that its writer has given up.
+    async fn release_abandoned_claim(&self, case_id: &String, revision: i64) -> Result<(), Error> {
+        let filter = doc! { "case_id": case_id, "revision": revision };
+        let claim = match self.changes.find_one(filter, None).await? {
+            Some(v) => v,
+            None => return Ok(())
+        };
+        if claim.timestamp > Utc::now().timestamp_millis() - CLAIM_TIMEOUT_MS {
+            return Ok(());
+        }
+        if self.read_case_revision(case_id).await?.unwrap_or_default() < revision {
+            println!("releasing abandoned claim on revision {} of case {}", revision, case_id);
+            self.release_claim(&claim).await?;
         }
+        Ok(())
     }
 
+}
 
+// matches a case at the revision, cases created before revisions were introduced don't have the field at all.
+fn current_revision(revision: i64) -> Document {
+    match revision {
+        0 => doc! { "$in": [0, Bson::Null] },
+        v => doc! { "$eq": v }
+    }
 }
 
+// whether an error is a write rejected by a unique index.
+fn is_duplicate(error: &Error) -> bool {
+    matches!(error.kind.as_ref(), ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == 11000)
+}
@@ -0,0 +1,53 @@
+use mongodb::{bson::doc, error::Error, options::FindOptions, Collection, Database, IndexModel};
+use rocket::futures::TryStreamExt;
+
+use crate::types::comment_database::Comment;
+
+
+pub struct CommentDatabase {
+    comments: Collection<Comment>
+}
+
+impl CommentDatabase {
+
+    pub async fn new(database: &Database) -> Self {
+        let comments = database.collection::<Comment>("comments");
+
+        let index = IndexModel::builder()
+            .keys(doc! { "case_id": 1, "created_at": 1 })
+            .build();
+        comments.create_index(index, None).await.unwrap();
+
+        Self {
+            comments
+        }
+    }
+
+    pub async fn create_comment(&self, comment: &Comment) -> Result<(), Error> {
+        self.comments.insert_one(comment, None).await?;
+        Ok(())
+    }
+
+    // reads the comments on a case, oldest first.
+    pub async fn read_comments(&self, case_id: &String) -> Result<Vec<Comment>, Error> {
+        let options = FindOptions::builder().sort(doc! { "created_at": 1 }).build();
+        let cursor = self.comments.find(doc! { "case_id": case_id }, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn read_comment(&self, case_id: &String, comment_id: &String) -> Result<Option<Comment>, Error> {
+        self.comments.find_one(doc! { "case_id": case_id, "comment_id": comment_id }, None).await
+    }
+
+    pub async fn delete_comment(&self, case_id: &String, comment_id: &String) -> Result<(), Error> {
+        self.comments.delete_one(doc! { "case_id": case_id, "comment_id": comment_id }, None).await?;
+        Ok(())
+    }
+
+    // deletes every comment on the case, used when the case itself is deleted.
+    pub async fn delete_comments_by_case(&self, case_id: &String) -> Result<(), Error> {
+        self.comments.delete_many(doc! { "case_id": case_id }, None).await?;
+        Ok(())
+    }
+
+}
@@ -1 +1,20 @@
-pub mod case;
\ No newline at end of file
+pub mod action;
+pub mod audit;
+pub mod case;
+pub mod comment;
+pub mod role;
+pub mod template;
+
+use mongodb::{options::ClientOptions, Client, Database};
+use std::env;
+
+
+// connects to the database all collections of the service live in.
+pub async fn connect() -> Database {
+    let client_uri = env::var("MONGODB_CONNECTION_STRING").unwrap();
+    let database_name = "core";
+
+    let client_options = ClientOptions::parse(client_uri).await.unwrap();
+    let client = Client::with_options(client_options).unwrap();
+    client.database(database_name)
+}
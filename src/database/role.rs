@@ -0,0 +1,63 @@
+use mongodb::{bson::{self, doc}, error::Error, options::{IndexOptions, ReplaceOptions}, Collection, Database, IndexModel};
+use rocket::futures::TryStreamExt;
+
+use crate::types::role_database::{Role, RoleAssignment};
+
+
+pub struct RoleDatabase {
+    roles: Collection<RoleAssignment>
+}
+
+impl RoleDatabase {
+
+    pub async fn new(database: &Database) -> Self {
+        let roles = database.collection::<RoleAssignment>("roles");
+
+        // a user has a single role on a case
+        let index = IndexModel::builder()
+            .keys(doc! { "case_id": 1, "user_id": 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        roles.create_index(index, None).await.unwrap();
+
+        Self {
+            roles
+        }
+    }
+
+    // the role of a member of the case's group. members without a role are viewers, except on cases nobody owns,
+    // such as cases from before roles, which every member of the group can edit until an admin gives them an owner.
+    pub async fn read_role(&self, case_id: &String, user_id: &String) -> Result<Role, Error> {
+        if let Some(assignment) = self.roles.find_one(doc! { "case_id": case_id, "user_id": user_id }, None).await? {
+            return Ok(assignment.role);
+        }
+        let owned = self.roles.find_one(doc! { "case_id": case_id, "role": bson::to_bson(&Role::Owner)? }, None).await?.is_some();
+        Ok(if owned { Role::Viewer } else { Role::Editor })
+    }
+
+    pub async fn read_roles(&self, case_id: &String) -> Result<Vec<RoleAssignment>, Error> {
+        let cursor = self.roles.find(doc! { "case_id": case_id }, None).await?;
+        cursor.try_collect().await
+    }
+
+    // gives the user the role on the case, replacing any role the user had.
+    pub async fn set_role(&self, assignment: &RoleAssignment) -> Result<(), Error> {
+        let filter = doc! { "case_id": &assignment.case_id, "user_id": &assignment.user_id };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.roles.replace_one(filter, assignment, options).await?;
+        Ok(())
+    }
+
+    // takes the user's role on the case, returning whether the user had one.
+    pub async fn delete_role(&self, case_id: &String, user_id: &String) -> Result<bool, Error> {
+        let result = self.roles.delete_one(doc! { "case_id": case_id, "user_id": user_id }, None).await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    // deletes every role on the case, used when the case itself is deleted.
+    pub async fn delete_roles_by_case(&self, case_id: &String) -> Result<(), Error> {
+        self.roles.delete_many(doc! { "case_id": case_id }, None).await?;
+        Ok(())
+    }
+
+}
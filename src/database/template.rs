@@ -0,0 +1,132 @@
+use mongodb::{bson::{doc, Document}, error::{Error, ErrorKind, WriteFailure}, options::{FindOneOptions, FindOptions, IndexOptions}, Collection, Database, IndexModel};
+use rocket::futures::TryStreamExt;
+use std::fs;
+use std::path::Path;
+use chrono::Utc;
+
+use crate::framework::FrameworkRegistry;
+use crate::types::template_database::TemplateMetadata;
+
+
+// the error code mongodb answers with when a unique index is violated.
+const DUPLICATE_KEY: i32 = 11000;
+
+pub struct TemplateDatabase {
+    templates: Collection<Document>,
+    templates_metadata: Collection<TemplateMetadata>
+}
+
+impl TemplateDatabase {
+
+    pub async fn new(database: &Database) -> Self {
+        let templates = database.collection::<Document>("templates");
+        let templates_metadata = database.collection::<TemplateMetadata>("templates");
+
+        // a version of a framework's template exists once per locale, templates from before versioning are left out
+        let index = IndexModel::builder()
+            .keys(doc! { "framework": 1, "version": 1, "locale": 1 })
+            .options(IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(doc! { "version": { "$exists": true } })
+                .build())
+            .build();
+        templates.create_index(index, None).await.unwrap();
+
+        Self {
+            templates,
+            templates_metadata
+        }
+    }
+
+    pub async fn read_templates(&self, framework: Option<&String>) -> Result<Vec<TemplateMetadata>, Error> {
+        let mut filter = doc! {};
+        if let Some(framework) = framework {
+            filter.insert("framework", framework);
+        }
+        let options = FindOptions::builder()
+            .projection(doc! { "framework": 1, "version": 1, "locale": 1, "updated_at": 1 })
+            .sort(doc! { "framework": 1, "version": -1 })
+            .build();
+        let cursor = self.templates_metadata.find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    // reads a framework's template, the latest version if no version is given.
+    pub async fn read_template(&self, framework: &str, version: Option<i32>, locale: Option<&String>) -> Result<Option<Document>, Error> {
+        let mut filter = doc! { "framework": framework };
+        if let Some(version) = version {
+            filter.insert("version", version);
+        }
+        if let Some(locale) = locale {
+            filter.insert("locale", locale);
+        }
+        let options = FindOneOptions::builder()
+            .projection(doc! { "_id": 0 })
+            .sort(doc! { "version": -1 })
+            .build();
+        self.templates.find_one(filter, options).await
+    }
+
+    // stores a new template, returning false if the version already exists for the locale.
+    pub async fn create_template(&self, framework: &str, version: i32, locale: &String, mut template: Document) -> Result<bool, Error> {
+        template.insert("framework", framework);
+        template.insert("version", version);
+        template.insert("locale", locale);
+        template.insert("updated_at", Utc::now().timestamp_millis());
+
+        match self.templates.insert_one(template, None).await {
+            Ok(_) => Ok(true),
+            Err(e) => match *e.kind {
+                ErrorKind::Write(WriteFailure::WriteError(ref error)) if error.code == DUPLICATE_KEY => Ok(false),
+                _ => Err(e)
+            }
+        }
+    }
+
+    // replaces the content of a template, returning false if there is no such template.
+    pub async fn replace_template(&self, framework: &str, version: i32, locale: &String, mut template: Document) -> Result<bool, Error> {
+        template.insert("framework", framework);
+        template.insert("version", version);
+        template.insert("locale", locale);
+        template.insert("updated_at", Utc::now().timestamp_millis());
+
+        let filter = doc! { "framework": framework, "version": version, "locale": locale };
+        let result = self.templates.replace_one(filter, template, None).await?;
+        Ok(result.matched_count == 1)
+    }
+
+    // stores the templates in a directory that the database doesn't have yet, so cases can be created on a fresh
+    // database. a template file names its framework, version and locale along with the content of the template.
+    // versions that exist already are left as they are, whether or not they were changed since.
+    pub async fn seed_templates(&self, frameworks: &FrameworkRegistry, directory: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|v| v != "json") {
+                continue;
+            }
+            let template = serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&path)?)
+                .map_err(|e| format!("invalid template {}: {}", path.display(), e))?;
+            let framework = template.get("framework").and_then(|v| v.as_str()).and_then(|v| frameworks.get(v));
+            let version = template.get("version").and_then(|v| v.as_i64()).and_then(|v| i32::try_from(v).ok());
+            let locale = template.get("locale").and_then(|v| v.as_str()).map(String::from);
+            let (framework, version, locale) = match (framework, version, locale) {
+                (Some(framework), Some(version), Some(locale)) => (framework, version, locale),
+                _ => return Err(format!("template {} needs a known framework, a version and a locale", path.display()).into())
+            };
+            let template = framework.parse_template(template)
+                .map_err(|e| format!("invalid template {}: {}", path.display(), e))?;
+            if self.create_template(framework.id(), version, &locale, template).await? {
+                println!("stored version {} of the {} template for {}", version, framework.id(), locale);
+            }
+        }
+        Ok(())
+    }
+
+    // deletes a template, returning false if there is no such template.
+    pub async fn delete_template(&self, framework: &str, version: i32, locale: &String) -> Result<bool, Error> {
+        let filter = doc! { "framework": framework, "version": version, "locale": locale };
+        let result = self.templates.delete_one(filter, None).await?;
+        Ok(result.deleted_count == 1)
+    }
+
+}
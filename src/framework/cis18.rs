@@ -0,0 +1,615 @@
+use mongodb::bson::{self, doc, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::framework::{average, gap, largest_gaps, parse_as, read_as, Framework};
+use crate::types::case_database::Documentation;
+use crate::types::framework::{CaseOptions, CaseScore, ControlScore, FieldKind, FieldLocation, FieldValues, ItemGap, MigrationItem, MigrationReport};
+
+
+// the highest score of a case whose template doesn't give the range of the scores.
+pub const MAX_SCORE: i32 = 5;
+
+// the cis controls v8, scored per safeguard on the cis maturity scale.
+pub struct CIS18Framework;
+
+impl Framework for CIS18Framework {
+    fn id(&self) -> &'static str {
+        "cis18"
+    }
+
+    fn parse_case(&self, case: Document) -> Result<Document, String> {
+        parse_as::<CIS18Case>(self.id(), case)
+    }
+
+    fn validate_options(&self, options: &CaseOptions) -> Result<(), String> {
+        match options.implementation_group {
+            Some(1..=3) => Ok(()),
+            Some(v) => Err(format!("{} is not an implementation group, expected 1, 2 or 3", v)),
+            None => Err("an implementation group is required".into())
+        }
+    }
+
+    // leaves out the safeguards outside of the implementation group, unless they are kept.
+    fn prepare_case(&self, case: &mut Document, options: &CaseOptions) -> Result<(), String> {
+        let implementation_group = options.implementation_group.unwrap_or(1);
+        let mut controls = match case.get("controls").cloned().map(bson::from_bson::<Vec<CIS18Control>>) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => return Err(format!("not a valid cis18 template: {}", e)),
+            None => return Err("not a valid cis18 template: missing controls".into())
+        };
+        if !options.keep_out_of_scope {
+            for control in controls.iter_mut() {
+                control.subcontrols.retain(|subcontrol| subcontrol.implementation_group.contains(&implementation_group));
+            }
+        }
+
+        case.insert("implementation_group", implementation_group);
+        case.insert("keep_out_of_scope", options.keep_out_of_scope);
+        case.insert("controls", bson::to_bson(&controls).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+
+    fn field_kind(&self, field: &str) -> Option<FieldKind> {
+        CIS18Field::parse(field).map(|v| v.kind())
+    }
+
+    // fields belong to a safeguard, found through its control and its own id.
+    fn locate(&self, ids: &[String], field: &str) -> Option<FieldLocation> {
+        match ids {
+            [control_id, subcontrol_id] => Some(location(control_id, subcontrol_id, field)),
+            _ => None
+        }
+    }
+
+    // a control has no editable fields, but what is attached to items can be attached to a whole control.
+    fn locate_item(&self, ids: &[String]) -> Option<FieldLocation> {
+        match ids {
+            [control_id] => Some(FieldLocation { arrays: vec![("controls", control_id.to_string())], field: "id".into() }),
+            _ => self.locate(ids, "id")
+        }
+    }
+
+    // safeguards are matched on their ids. safeguards the template no longer has are retired, and out of scope
+    // safeguards are hidden or kept the same way they are when the case is created.
+    fn migrate(&self, case: Document, template: Document) -> Result<(Document, MigrationReport), String> {
+        let mut case = read_as::<CIS18Case>(self.id(), &case)?;
+        let template = match bson::from_document::<CIS18Case>(template) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("not a valid cis18 template: {}", e))
+        };
+
+        // every safeguard the case has, wherever it is kept, and whether it was retired already
+        let mut existing: Vec<(HiddenSubControl, bool)> = Vec::new();
+        for control in case.controls.drain(..) {
+            existing.extend(control.subcontrols.into_iter().map(|subcontrol| (HiddenSubControl { control_id: control.id.to_string(), subcontrol }, false)));
+        }
+        existing.extend(case.hidden_subcontrols.drain(..).map(|v| (v, false)));
+        existing.extend(case.retired_subcontrols.drain(..).map(|v| (v, true)));
+
+        let mut report = MigrationReport::default();
+        for template_control in template.controls {
+            let mut subcontrols = Vec::new();
+            for mut subcontrol in template_control.subcontrols {
+                match existing.iter().position(|(v, _)| v.subcontrol.id == subcontrol.id) {
+                    Some(i) => {
+                        let (old, _) = existing.remove(i);
+                        let old = old.subcontrol;
+                        if old.title != subcontrol.title {
+                            report.renamed.push(item(&template_control.id, &subcontrol, Some(old.title)));
+                        }
+                        subcontrol.observation = old.observation;
+                        subcontrol.plan = old.plan;
+                        subcontrol.as_is_score = old.as_is_score;
+                        subcontrol.to_be_score = old.to_be_score;
+                        subcontrol.soa = old.soa;
+                        subcontrol.documentation = old.documentation;
+                        report.carried_over += 1;
+                    },
+                    None => report.added.push(item(&template_control.id, &subcontrol, None))
+                };
+
+                if case.keep_out_of_scope || subcontrol.implementation_group.contains(&case.implementation_group) {
+                    subcontrols.push(subcontrol);
+                } else {
+                    case.hidden_subcontrols.push(HiddenSubControl { control_id: template_control.id.to_string(), subcontrol });
+                }
+            }
+            case.controls.push(CIS18Control {
+                id: template_control.id,
+                title: template_control.title,
+                description: template_control.description,
+                subcontrols
+            });
+        }
+
+        // what the template no longer has is retired, a new safeguard with the same title likely replaces it
+        for (retired, already) in existing {
+            if !already {
+                let mut removed = item(&retired.control_id, &retired.subcontrol, None);
+                removed.replaced_by = report.added.iter()
+                    .find(|v| v.title == retired.subcontrol.title)
+                    .and_then(|v| v.subcontrol_id.clone());
+                report.removed.push(removed);
+            }
+            case.retired_subcontrols.push(retired);
+        }
+
+        let fields = doc! {
+            "controls": bson::to_bson(&case.controls).map_err(|e| e.to_string())?,
+            "hidden_subcontrols": bson::to_bson(&case.hidden_subcontrols).map_err(|e| e.to_string())?,
+            "retired_subcontrols": bson::to_bson(&case.retired_subcontrols).map_err(|e| e.to_string())?,
+            "field_values": bson::to_bson(&template.field_values).map_err(|e| e.to_string())?
+        };
+        Ok((fields, report))
+    }
+
+    // averages the scores of the subcontrols in the case's implementation group that are not marked not applicable.
+    // coverage weighs the subcontrols by the lowest implementation group they belong to, as the ones every
+    // organisation is expected to have in place count the most.
+    fn score(&self, case: &Document) -> Result<CaseScore, String> {
+        let case = read_as::<CIS18Case>(self.id(), case)?;
+        let in_scope = |subcontrol: &&CIS18SubControl| {
+            subcontrol.implementation_group.contains(&case.implementation_group) && subcontrol.soa != "not_applicable"
+        };
+
+        let controls = case.controls.iter()
+            .map(|control| {
+                let current = average(control.subcontrols.iter().filter(in_scope).map(|v| v.as_is_score as f64));
+                let target = average(control.subcontrols.iter().filter(in_scope).map(|v| v.to_be_score as f64));
+                ControlScore { id: control.id.to_string(), title: control.title.to_string(), current, target, gap: gap(current, target) }
+            })
+            .collect();
+        let subcontrols = || case.controls.iter()
+            .flat_map(|control| control.subcontrols.iter().map(move |subcontrol| (control, subcontrol)))
+            .filter(|(_, subcontrol)| in_scope(subcontrol));
+
+        let max_score = max_score(&case) as f64;
+        let (achieved, possible) = subcontrols().fold((0.0, 0.0), |(achieved, possible), (_, subcontrol)| {
+            let weight = weight(subcontrol);
+            (achieved + weight * subcontrol.as_is_score as f64, possible + weight * max_score)
+        });
+        let gaps = subcontrols()
+            .map(|(control, subcontrol)| ItemGap {
+                control_id: control.id.to_string(),
+                subcontrol_id: Some(subcontrol.id.to_string()),
+                title: subcontrol.title.to_string(),
+                current: subcontrol.as_is_score as f64,
+                target: subcontrol.to_be_score as f64,
+                gap: (subcontrol.to_be_score - subcontrol.as_is_score) as f64
+            })
+            .collect();
+
+        let current = average(subcontrols().map(|(_, v)| v.as_is_score as f64));
+        let target = average(subcontrols().map(|(_, v)| v.to_be_score as f64));
+        Ok(CaseScore {
+            max: max_score,
+            current,
+            target,
+            gap: gap(current, target),
+            coverage: Some(achieved / possible).filter(|_| possible > 0.0),
+            controls,
+            largest_gaps: largest_gaps(gaps)
+        })
+    }
+}
+
+// the highest score the case can give, the top of the range its template gives for the as is score.
+pub fn max_score(case: &CIS18Case) -> i32 {
+    match case.field_values.get("as_is_score") {
+        Some(FieldValues::Range { max, .. }) => *max,
+        _ => MAX_SCORE
+    }
+}
+
+// how much a subcontrol weighs in the coverage of a case, 3 for implementation group 1 down to 1 for group 3.
+fn weight(subcontrol: &CIS18SubControl) -> f64 {
+    match subcontrol.implementation_group.iter().min() {
+        Some(1) => 3.0,
+        Some(2) => 2.0,
+        _ => 1.0
+    }
+}
+
+// moves the safeguards of a case in and out of scope for a new implementation group.
+// safeguards leaving the scope are hidden along with what was entered on them, and come back as they were if they
+// come into scope again. safeguards coming into scope for the first time are taken from the template.
+// a case keeping its out of scope safeguards only gets the ones it is missing, whether they are in scope follows
+// from the implementation group alone.
+pub fn change_implementation_group(case: &mut CIS18Case, template: CIS18Case, implementation_group: i32) {
+    let in_scope = |subcontrol: &CIS18SubControl| subcontrol.implementation_group.contains(&implementation_group);
+    case.implementation_group = implementation_group;
+
+    if !case.keep_out_of_scope {
+        for control in case.controls.iter_mut() {
+            let (kept, hidden): (Vec<_>, Vec<_>) = control.subcontrols.drain(..).partition(in_scope);
+            control.subcontrols = kept;
+            case.hidden_subcontrols.extend(hidden.into_iter().map(|subcontrol| HiddenSubControl { control_id: control.id.to_string(), subcontrol }));
+        }
+    }
+
+    let (returning, hidden): (Vec<_>, Vec<_>) = case.hidden_subcontrols.drain(..)
+        .partition(|hidden| case.keep_out_of_scope || in_scope(&hidden.subcontrol));
+    case.hidden_subcontrols = hidden;
+    for hidden in returning {
+        if let Some(control) = case.controls.iter_mut().find(|control| control.id == hidden.control_id) {
+            control.subcontrols.push(hidden.subcontrol);
+        }
+    }
+
+    for template_control in template.controls {
+        let position = match case.controls.iter().position(|control| control.id == template_control.id) {
+            Some(v) => v,
+            None => {
+                case.controls.push(CIS18Control {
+                    id: template_control.id.to_string(),
+                    title: template_control.title.to_string(),
+                    description: template_control.description.to_string(),
+                    subcontrols: Vec::new()
+                });
+                case.controls.len() - 1
+            }
+        };
+        let order: Vec<String> = template_control.subcontrols.iter().map(|subcontrol| subcontrol.id.to_string()).collect();
+
+        for subcontrol in template_control.subcontrols {
+            let known = case.controls[position].subcontrols.iter().any(|v| v.id == subcontrol.id)
+                || case.hidden_subcontrols.iter().any(|v| v.subcontrol.id == subcontrol.id);
+            if known {
+                continue;
+            }
+            if case.keep_out_of_scope || in_scope(&subcontrol) {
+                case.controls[position].subcontrols.push(subcontrol);
+            }
+        }
+
+        // keep the safeguards in the order of the template, anything the template doesn't know goes last
+        case.controls[position].subcontrols.sort_by_key(|subcontrol| order.iter().position(|id| *id == subcontrol.id).unwrap_or(order.len()));
+    }
+}
+
+fn item(control_id: &str, subcontrol: &CIS18SubControl, old_title: Option<String>) -> MigrationItem {
+    MigrationItem {
+        control_id: control_id.to_string(),
+        subcontrol_id: Some(subcontrol.id.to_string()),
+        title: subcontrol.title.to_string(),
+        old_title,
+        replaced_by: None
+    }
+}
+
+// where a subcontrol field sits in a cis18 case.
+pub fn location(control_id: &str, subcontrol_id: &str, field: &str) -> FieldLocation {
+    FieldLocation {
+        arrays: vec![("controls", control_id.to_string()), ("subcontrols", subcontrol_id.to_string())],
+        field: field.to_string()
+    }
+}
+
+// the subcontrol fields clients are allowed to edit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CIS18Field {
+    Observation,
+    Plan,
+    Soa,
+    AsIsScore,
+    ToBeScore,
+    Documentation
+}
+
+impl CIS18Field {
+    pub fn parse(field: &str) -> Option<Self> {
+        match field {
+            "observation" => Some(Self::Observation),
+            "plan" => Some(Self::Plan),
+            "soa" => Some(Self::Soa),
+            "as_is_score" => Some(Self::AsIsScore),
+            "to_be_score" => Some(Self::ToBeScore),
+            "documentation" => Some(Self::Documentation),
+            _ => None
+        }
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Self::Observation | Self::Plan => FieldKind::Text,
+            Self::Soa => FieldKind::Choice,
+            Self::AsIsScore | Self::ToBeScore => FieldKind::Score,
+            Self::Documentation => FieldKind::Documents
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CIS18Case {
+    pub case_id: String,
+    pub group_id: String,
+    pub name: String,
+    pub implementation_group: i32,
+    #[serde(default)]
+    pub revision: i64,
+    // the version and locale of the template the case was created from, unknown for cases from before template versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_version: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_locale: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub field_values: HashMap<String, FieldValues>,
+    // whether safeguards outside of the implementation group are kept in the case rather than hidden. kept safeguards
+    // are out of scope while the implementation group isn't one of theirs, whatever their soa says, and leave the
+    // score and projections out the same way hidden ones do
+    #[serde(default)]
+    pub keep_out_of_scope: bool,
+    pub controls: Vec<CIS18Control>,
+    // safeguards that fell out of the implementation group, kept with what was entered on them in case they come back
+    #[serde(default)]
+    pub hidden_subcontrols: Vec<HiddenSubControl>,
+    // safeguards a newer template no longer has, kept with what was entered on them when the case was migrated
+    #[serde(default)]
+    pub retired_subcontrols: Vec<HiddenSubControl>
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CIS18Control {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub subcontrols: Vec<CIS18SubControl>
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HiddenSubControl {
+    pub control_id: String,
+    pub subcontrol: CIS18SubControl
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CIS18SubControl {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub observation: String,
+    pub as_is_score: i32,
+    pub plan: String,
+    pub to_be_score: i32,
+    pub soa: String,
+    pub implementation_group: Vec<i32>,
+    pub documentation: Vec<Documentation>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subcontrol(id: &str, implementation_group: &[i32]) -> CIS18SubControl {
+        CIS18SubControl {
+            id: id.into(),
+            title: format!("safeguard {}", id),
+            description: String::new(),
+            observation: String::new(),
+            as_is_score: 0,
+            plan: String::new(),
+            to_be_score: 0,
+            soa: String::new(),
+            implementation_group: implementation_group.to_vec(),
+            documentation: Vec::new()
+        }
+    }
+
+    // a case of one control with a safeguard in every implementation group, one only from group 2 on and one only in group 3.
+    fn case(implementation_group: i32, keep_out_of_scope: bool) -> CIS18Case {
+        CIS18Case {
+            case_id: "case".into(),
+            group_id: "group".into(),
+            name: "case".into(),
+            implementation_group,
+            revision: 0,
+            template_version: Some(1),
+            template_locale: Some("en".into()),
+            field_values: HashMap::new(),
+            keep_out_of_scope,
+            controls: vec![CIS18Control {
+                id: "1".into(),
+                title: "control 1".into(),
+                description: String::new(),
+                subcontrols: vec![subcontrol("1.1", &[1, 2, 3]), subcontrol("1.2", &[2, 3]), subcontrol("1.3", &[3])]
+            }],
+            hidden_subcontrols: Vec::new(),
+            retired_subcontrols: Vec::new()
+        }
+    }
+
+    // the case as created from the template for the implementation group, the way prepare_case would.
+    fn created(implementation_group: i32, keep_out_of_scope: bool) -> CIS18Case {
+        let mut case = case(implementation_group, keep_out_of_scope);
+        if !keep_out_of_scope {
+            case.controls[0].subcontrols.retain(|v| v.implementation_group.contains(&implementation_group));
+        }
+        case
+    }
+
+    fn ids(case: &CIS18Case) -> Vec<&str> {
+        case.controls.iter().flat_map(|v| v.subcontrols.iter().map(|v| v.id.as_str())).collect()
+    }
+
+    fn find<'a>(case: &'a CIS18Case, id: &str) -> &'a CIS18SubControl {
+        case.controls.iter().flat_map(|v| v.subcontrols.iter()).find(|v| v.id == id).unwrap()
+    }
+
+    fn find_mut<'a>(case: &'a mut CIS18Case, id: &str) -> &'a mut CIS18SubControl {
+        case.controls.iter_mut().flat_map(|v| v.subcontrols.iter_mut()).find(|v| v.id == id).unwrap()
+    }
+
+    #[test]
+    fn raising_the_implementation_group_adds_safeguards_from_the_template() {
+        let mut case = created(1, false);
+        find_mut(&mut case, "1.1").observation = "in place".into();
+
+        change_implementation_group(&mut case, self::case(1, false), 2);
+        assert_eq!(case.implementation_group, 2);
+        assert_eq!(ids(&case), vec!["1.1", "1.2"]);
+        assert_eq!(find(&case, "1.1").observation, "in place");
+        assert!(case.hidden_subcontrols.is_empty());
+    }
+
+    #[test]
+    fn safeguards_leaving_the_scope_come_back_as_they_were() {
+        let mut case = created(1, false);
+        change_implementation_group(&mut case, self::case(1, false), 2);
+        find_mut(&mut case, "1.2").observation = "partly".into();
+        find_mut(&mut case, "1.2").soa = "applicable".into();
+
+        change_implementation_group(&mut case, self::case(1, false), 1);
+        assert_eq!(ids(&case), vec!["1.1"]);
+        assert_eq!(case.hidden_subcontrols.len(), 1);
+        assert_eq!(case.hidden_subcontrols[0].control_id, "1");
+        assert_eq!(case.hidden_subcontrols[0].subcontrol.id, "1.2");
+
+        change_implementation_group(&mut case, self::case(1, false), 2);
+        assert_eq!(ids(&case), vec!["1.1", "1.2"]);
+        assert_eq!(find(&case, "1.2").observation, "partly");
+        assert_eq!(find(&case, "1.2").soa, "applicable");
+        assert!(case.hidden_subcontrols.is_empty());
+    }
+
+    #[test]
+    fn kept_safeguards_stay_as_they_were_when_the_scope_changes() {
+        let mut case = created(1, true);
+        assert_eq!(ids(&case), vec!["1.1", "1.2", "1.3"]);
+        find_mut(&mut case, "1.2").soa = "applicable".into();
+        find_mut(&mut case, "1.3").soa = "applicable".into();
+
+        change_implementation_group(&mut case, self::case(1, true), 2);
+        change_implementation_group(&mut case, self::case(1, true), 1);
+        assert_eq!(case.implementation_group, 1);
+        assert_eq!(ids(&case), vec!["1.1", "1.2", "1.3"]);
+        assert_eq!(find(&case, "1.2").soa, "applicable");
+        assert_eq!(find(&case, "1.3").soa, "applicable");
+        assert!(case.hidden_subcontrols.is_empty());
+    }
+
+    #[test]
+    fn kept_safeguards_are_scored_by_the_implementation_group() {
+        let mut case = created(1, true);
+        for (id, score) in [("1.1", 4), ("1.2", 2), ("1.3", 0)] {
+            find_mut(&mut case, id).as_is_score = score;
+        }
+        let score = |case: &CIS18Case| CIS18Framework.score(&bson::to_document(case).unwrap()).unwrap().current;
+
+        assert_eq!(score(&case), Some(4.0));
+        change_implementation_group(&mut case, self::case(1, true), 2);
+        assert_eq!(score(&case), Some(3.0));
+        change_implementation_group(&mut case, self::case(1, true), 1);
+        assert_eq!(score(&case), Some(4.0));
+    }
+
+    // migrates the case onto the template, returning the case as it is stored afterwards.
+    fn migrate(case: &CIS18Case, template: &CIS18Case) -> (CIS18Case, MigrationReport) {
+        let mut case = bson::to_document(case).unwrap();
+        let (fields, report) = CIS18Framework.migrate(case.clone(), bson::to_document(template).unwrap()).unwrap();
+        case.extend(fields);
+        (bson::from_document(case).unwrap(), report)
+    }
+
+    fn retired(case: &CIS18Case) -> Vec<&str> {
+        case.retired_subcontrols.iter().map(|v| v.subcontrol.id.as_str()).collect()
+    }
+
+    #[test]
+    fn migrating_keeps_what_was_entered_on_matching_safeguards() {
+        let mut case = created(3, false);
+        find_mut(&mut case, "1.2").observation = "partly".into();
+        find_mut(&mut case, "1.2").as_is_score = 2;
+        let mut template = self::case(1, false);
+        find_mut(&mut template, "1.2").title = "renamed".into();
+
+        let (case, report) = migrate(&case, &template);
+        assert_eq!(ids(&case), vec!["1.1", "1.2", "1.3"]);
+        assert_eq!(find(&case, "1.2").title, "renamed");
+        assert_eq!(find(&case, "1.2").observation, "partly");
+        assert_eq!(find(&case, "1.2").as_is_score, 2);
+        assert_eq!(report.carried_over, 3);
+        assert!(report.added.is_empty() && report.removed.is_empty());
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].old_title.as_deref(), Some("safeguard 1.2"));
+    }
+
+    #[test]
+    fn migrating_retires_safeguards_the_template_no_longer_has() {
+        let mut case = created(3, false);
+        find_mut(&mut case, "1.3").observation = "in place".into();
+        let mut template = self::case(1, false);
+        template.controls[0].subcontrols.retain(|v| v.id != "1.3");
+
+        let (case, report) = migrate(&case, &template);
+        assert_eq!(ids(&case), vec!["1.1", "1.2"]);
+        assert_eq!(retired(&case), vec!["1.3"]);
+        assert_eq!(case.retired_subcontrols[0].subcontrol.observation, "in place");
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(report.removed[0].subcontrol_id.as_deref(), Some("1.3"));
+        assert_eq!(report.removed[0].replaced_by, None);
+    }
+
+    #[test]
+    fn migrating_brings_back_retired_safeguards_the_template_has_again() {
+        let mut case = created(3, false);
+        find_mut(&mut case, "1.3").observation = "in place".into();
+        let mut without = self::case(1, false);
+        without.controls[0].subcontrols.retain(|v| v.id != "1.3");
+        let (case, _) = migrate(&case, &without);
+
+        let (case, report) = migrate(&case, &self::case(1, false));
+        assert_eq!(ids(&case), vec!["1.1", "1.2", "1.3"]);
+        assert_eq!(find(&case, "1.3").observation, "in place");
+        assert!(case.retired_subcontrols.is_empty());
+        assert_eq!(report.carried_over, 3);
+        assert!(report.added.is_empty() && report.removed.is_empty());
+
+        // retired once, a safeguard isn't reported as removed again by later migrations
+        let (case, report) = migrate(&case, &without);
+        let (case, again) = migrate(&case, &without);
+        assert_eq!(report.removed.len(), 1);
+        assert!(again.removed.is_empty());
+        assert_eq!(retired(&case), vec!["1.3"]);
+    }
+
+    #[test]
+    fn renumbered_safeguards_are_replaced_by_the_one_with_their_title() {
+        let case = created(3, false);
+        let mut template = self::case(1, false);
+        template.controls[0].subcontrols[2].id = "1.4".into();
+
+        let (case, report) = migrate(&case, &template);
+        assert_eq!(ids(&case), vec!["1.1", "1.2", "1.4"]);
+        assert_eq!(retired(&case), vec!["1.3"]);
+        assert_eq!(report.added.len(), 1);
+        assert_eq!(report.added[0].subcontrol_id.as_deref(), Some("1.4"));
+        assert_eq!(report.removed[0].replaced_by.as_deref(), Some("1.4"));
+    }
+
+    #[test]
+    fn migrating_hides_safeguards_outside_of_the_implementation_group() {
+        let case = created(1, false);
+        let mut template = self::case(1, false);
+        template.controls[0].subcontrols.push(subcontrol("1.5", &[2, 3]));
+
+        let (case, report) = migrate(&case, &template);
+        assert_eq!(ids(&case), vec!["1.1"]);
+        let hidden: Vec<&str> = case.hidden_subcontrols.iter().map(|v| v.subcontrol.id.as_str()).collect();
+        assert_eq!(hidden, vec!["1.2", "1.3", "1.5"]);
+        assert_eq!(report.added.len(), 3);
+    }
+
+    #[test]
+    fn items_are_safeguards_or_whole_controls() {
+        let case = bson::to_document(&created(1, false)).unwrap();
+        let ids = |ids: &[&str]| ids.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert!(CIS18Framework.has_item(&case, &ids(&["1"])));
+        assert!(CIS18Framework.has_item(&case, &ids(&["1", "1.1"])));
+        assert!(!CIS18Framework.has_item(&case, &ids(&["1", "1.2"])));
+        assert!(!CIS18Framework.has_item(&case, &ids(&["2"])));
+        assert!(!CIS18Framework.has_item(&case, &ids(&[])));
+        // controls have no fields of their own to edit
+        assert!(CIS18Framework.locate(&ids(&["1"]), "observation").is_none());
+    }
+}
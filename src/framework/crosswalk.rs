@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::framework::average;
+use crate::framework::cis18::max_score;
+use crate::framework::cis18::{CIS18Case, CIS18SubControl};
+use crate::types::collaboration_handler::MAX_TEXT_LENGTH;
+use crate::types::crosswalk::{Crosswalk, ProjectedItem};
+
+
+// the crosswalks between frameworks, loaded from the json files in a directory at startup.
+#[derive(Debug, Default)]
+pub struct CrosswalkRegistry {
+    crosswalks: Vec<Crosswalk>
+}
+
+impl CrosswalkRegistry {
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let mut crosswalks = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|v| v == "json") {
+                let crosswalk = serde_json::from_str::<Crosswalk>(&fs::read_to_string(&path)?)
+                    .map_err(|e| format!("invalid crosswalk {}: {}", path.display(), e))?;
+                crosswalks.push(crosswalk);
+            }
+        }
+        Ok(Self { crosswalks })
+    }
+
+    pub fn get(&self, source: &str, target: &str) -> Option<&Crosswalk> {
+        self.crosswalks.iter().find(|v| v.source == source && v.target == target)
+    }
+}
+
+// projects the safeguards of a cis18 case onto the items of another framework. scores are averaged over the safeguards
+// in scope, the same ones the case is scored on, and what was observed, planned and documented on them is gathered
+// under the safeguard it came from. items none of the case's safeguards map onto are left out.
+pub fn project(crosswalk: &Crosswalk, case: &CIS18Case) -> Vec<ProjectedItem> {
+    let mut sources: BTreeMap<&str, Vec<&CIS18SubControl>> = BTreeMap::new();
+    for mapping in &crosswalk.mappings {
+        let subcontrols = case.controls.iter()
+            .flat_map(|control| control.subcontrols.iter().map(move |subcontrol| (control, subcontrol)))
+            .filter(|(control, subcontrol)| control.id == mapping.source || subcontrol.id == mapping.source)
+            .map(|(_, subcontrol)| subcontrol);
+        for subcontrol in subcontrols {
+            for target in &mapping.targets {
+                let mapped = sources.entry(target.as_str()).or_default();
+                if !mapped.iter().any(|v| v.id == subcontrol.id) {
+                    mapped.push(subcontrol);
+                }
+            }
+        }
+    }
+
+    let max_score = max_score(case) as f64;
+    sources.into_iter()
+        .map(|(id, subcontrols)| {
+            let applicable = || subcontrols.iter()
+                .filter(|v| v.implementation_group.contains(&case.implementation_group) && v.soa != "not_applicable");
+            ProjectedItem {
+                id: id.to_string(),
+                sources: subcontrols.iter().map(|v| v.id.to_string()).collect(),
+                current: average(applicable().map(|v| v.as_is_score as f64)).map(|v| v / max_score),
+                target: average(applicable().map(|v| v.to_be_score as f64)).map(|v| v / max_score),
+                not_applicable: applicable().next().is_none(),
+                observation: gather(applicable().map(|v| (&v.id, &v.observation))),
+                plan: gather(applicable().map(|v| (&v.id, &v.plan))),
+                evidence: applicable().flat_map(|v| v.documentation.iter().cloned()).collect()
+            }
+        })
+        .collect()
+}
+
+// the non-empty texts, each under the id of the safeguard it came from, cut off to fit a text field.
+fn gather<'a>(texts: impl Iterator<Item = (&'a String, &'a String)>) -> String {
+    let text = texts
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(id, text)| format!("CIS {}: {}", id, text.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    text.chars().take(MAX_TEXT_LENGTH).collect()
+}
@@ -0,0 +1,160 @@
+use mongodb::bson::{self, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::framework::{average, parse_as, read_as, Framework};
+use crate::types::case_database::Documentation;
+use crate::types::crosswalk::ProjectedItem;
+use crate::types::framework::{CaseScore, ControlScore, FieldKind, FieldLocation, FieldValues};
+
+
+// the annex a controls of iso/iec 27001:2022, assessed through a statement of applicability.
+// controls are numbered after their theme, e.g. control 8.12 belongs to theme 8.
+pub struct ISO27001Framework;
+
+impl Framework for ISO27001Framework {
+    fn id(&self) -> &'static str {
+        "iso27001"
+    }
+
+    fn parse_case(&self, case: Document) -> Result<Document, String> {
+        parse_as::<ISO27001Case>(self.id(), case)
+    }
+
+    fn field_kind(&self, field: &str) -> Option<FieldKind> {
+        ISO27001Field::parse(field).map(|v| v.kind())
+    }
+
+    fn locate(&self, ids: &[String], field: &str) -> Option<FieldLocation> {
+        match ids {
+            [control_id] => location(control_id, field),
+            _ => None
+        }
+    }
+
+    // the share of the applicable controls that are implemented, per theme and overall, a control in progress counting as half.
+    fn score(&self, case: &Document) -> Result<CaseScore, String> {
+        let case = read_as::<ISO27001Case>(self.id(), case)?;
+        let progress = |control: &ISO27001Control| match (control.applicability.as_str(), control.status.as_str()) {
+            ("not_applicable", _) => None,
+            (_, "implemented") => Some(1.0),
+            (_, "in_progress") => Some(0.5),
+            _ => Some(0.0)
+        };
+
+        let controls = case.themes.iter()
+            .map(|theme| ControlScore {
+                id: theme.id.to_string(),
+                title: theme.title.to_string(),
+                current: average(theme.controls.iter().filter_map(progress)),
+                target: None,
+                gap: None
+            })
+            .collect();
+
+        Ok(CaseScore {
+            max: 1.0,
+            current: average(case.themes.iter().flat_map(|theme| theme.controls.iter()).filter_map(progress)),
+            target: None,
+            gap: None,
+            coverage: None,
+            controls,
+            largest_gaps: Vec::new()
+        })
+    }
+
+    // controls take their applicability and status from what is projected onto them, with the observations
+    // made as the justification.
+    fn seed(&self, case: &mut Document, items: &[ProjectedItem]) -> Result<(), String> {
+        let mut themes = match case.get("themes").cloned().map(bson::from_bson::<Vec<ISO27001Theme>>) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => return Err(format!("not a valid iso27001 template: {}", e)),
+            None => return Err("not a valid iso27001 template: missing themes".into())
+        };
+        for control in themes.iter_mut().flat_map(|theme| theme.controls.iter_mut()) {
+            if let Some(item) = items.iter().find(|v| v.id == control.id) {
+                control.applicability = if item.not_applicable { "not_applicable".into() } else { "applicable".into() };
+                control.justification = item.observation.to_string();
+                control.status = item.status().into();
+                control.evidence = item.evidence.clone();
+            }
+        }
+        case.insert("themes", bson::to_bson(&themes).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+}
+
+// where a control field sits in an iso 27001 case, None if the control id doesn't name a theme.
+pub fn location(control_id: &str, field: &str) -> Option<FieldLocation> {
+    let (theme_id, _) = control_id.split_once('.')?;
+    Some(FieldLocation {
+        arrays: vec![("themes", theme_id.to_string()), ("controls", control_id.to_string())],
+        field: field.to_string()
+    })
+}
+
+// the control fields clients are allowed to edit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ISO27001Field {
+    Applicability,
+    Justification,
+    Status,
+    Evidence
+}
+
+impl ISO27001Field {
+    pub fn parse(field: &str) -> Option<Self> {
+        match field {
+            "applicability" => Some(Self::Applicability),
+            "justification" => Some(Self::Justification),
+            "status" => Some(Self::Status),
+            "evidence" => Some(Self::Evidence),
+            _ => None
+        }
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Self::Applicability => FieldKind::Choice,
+            Self::Justification => FieldKind::Text,
+            Self::Status => FieldKind::Choice,
+            Self::Evidence => FieldKind::Documents
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ISO27001Case {
+    pub case_id: String,
+    pub name: String,
+    pub group_id: String,
+    #[serde(default)]
+    pub revision: i64,
+    // the version and locale of the template the case was created from, unknown for cases from before template versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_version: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_locale: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub field_values: HashMap<String, FieldValues>,
+    pub themes: Vec<ISO27001Theme>
+}
+
+// one of the four themes the annex a controls of iso/iec 27001:2022 are grouped into.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ISO27001Theme {
+    pub id: String,
+    pub title: String,
+    pub controls: Vec<ISO27001Control>
+}
+
+// an annex a control along with its statement of applicability entry.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ISO27001Control {
+    pub id: String,
+    pub title: String,
+    pub applicability: String,
+    pub justification: String,
+    pub status: String,
+    pub evidence: Vec<Documentation>
+}
@@ -0,0 +1,249 @@
+use mongodb::bson::{self, Bson, Document};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+
+use crate::types::case_database::Documentation;
+use crate::types::collaboration_handler::{Change, TextOrIntValue, MAX_TEXT_LENGTH};
+use crate::types::crosswalk::ProjectedItem;
+use crate::types::framework::{CaseOptions, CaseScore, FieldKind, FieldLocation, FieldValues, ItemGap, MigrationReport};
+
+pub mod cis18;
+pub mod crosswalk;
+pub mod iso27001;
+pub mod nis2;
+pub mod nistcsf;
+
+
+// everything that differs between the frameworks a case can follow. cases are passed around as the documents they
+// are stored as, each framework reads them the way it lays them out, so adding a framework takes its module and
+// registering it at startup.
+// a framework is registered under its id, which is what its cases and templates are stored with.
+pub trait Framework: Send + Sync {
+    fn id(&self) -> &'static str;
+
+    // checks that a document makes up a case of the framework, returning it the way it is stored.
+    // what the framework doesn't know about is left out.
+    fn parse_case(&self, case: Document) -> Result<Document, String>;
+
+    // checks the choices made when creating a case before the template is read.
+    fn validate_options(&self, _options: &CaseOptions) -> Result<(), String> {
+        Ok(())
+    }
+
+    // turns a copy of the framework's template into a new case. the ids, name and revision are already set.
+    fn prepare_case(&self, _case: &mut Document, _options: &CaseOptions) -> Result<(), String> {
+        Ok(())
+    }
+
+    // the kind of value an editable field holds, None if the field can't be edited.
+    fn field_kind(&self, field: &str) -> Option<FieldKind>;
+
+    // where a field of an item sits in a case of the framework, the ids leading to the item outermost first.
+    fn locate(&self, ids: &[String], field: &str) -> Option<FieldLocation>;
+
+    // where the id of an item sits in a case of the framework, for what is attached to items rather than set on
+    // their fields, such as action items. these are the items with editable fields unless the framework says otherwise.
+    fn locate_item(&self, ids: &[String]) -> Option<FieldLocation> {
+        self.locate(ids, "id")
+    }
+
+    // whether the case has the item the ids lead to.
+    fn has_item(&self, case: &Document, ids: &[String]) -> bool {
+        self.locate_item(ids)
+            .and_then(|location| location.read(case))
+            .is_some_and(|id| !matches!(id, Bson::Null))
+    }
+
+    // how far the case has come.
+    fn score(&self, case: &Document) -> Result<CaseScore, String>;
+
+    // moves a case onto another version of the framework's template, carrying over what was entered on it.
+    // returns the fields of the case to replace along with what changes, or why the case can't be migrated.
+    fn migrate(&self, _case: Document, _template: Document) -> Result<(Document, MigrationReport), String> {
+        Err(format!("migrating is not supported for {} cases", self.id()))
+    }
+
+    // fills in a copy of the framework's template from what another case says about its items, through a crosswalk.
+    // items without a projection are left as the template has them.
+    fn seed(&self, _case: &mut Document, _items: &[ProjectedItem]) -> Result<(), String> {
+        Err(format!("{} cases can't be seeded from another framework", self.id()))
+    }
+
+    // the case as the export service expects it.
+    fn export(&self, case: &Document) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(case)
+    }
+
+    // checks that an uploaded template makes up a case of the framework, returning it the way it is stored.
+    // the ids and name of the case are filled in when a case is created from it, so they can be left out.
+    // the values it lists for a field have to fit the kind of field.
+    fn parse_template(&self, template: serde_json::Value) -> Result<Document, String> {
+        let mut template = match bson::to_document(&template) {
+            Ok(v) => v,
+            Err(_) => return Err("a template must be an object".into())
+        };
+        for key in ["case_id", "group_id", "name"] {
+            if !template.contains_key(key) {
+                template.insert(key, "");
+            }
+        }
+        let template = match self.parse_case(template) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("not a valid {} template: {}", self.id(), e))
+        };
+        let field_values = match template.get("field_values").cloned().map(bson::from_bson::<HashMap<String, FieldValues>>) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => return Err(format!("not a valid {} template: {}", self.id(), e)),
+            None => HashMap::new()
+        };
+        for (field, values) in &field_values {
+            let fits = match (self.field_kind(field), values) {
+                (Some(FieldKind::Choice), FieldValues::Choices(choices)) => !choices.is_empty(),
+                (Some(FieldKind::Score), FieldValues::Range { min, max }) => min <= max,
+                _ => false
+            };
+            if !fits {
+                return Err(format!("not a valid {} template: {:?} are not values {} can take", self.id(), values, field));
+            }
+        }
+        Ok(template)
+    }
+
+    // checks that the field can be edited and that the change fits its kind, returning where it lands in the case.
+    // whether the value is one the case allows is up to check_allowed, as that depends on the case.
+    fn validate(&self, change: &Change) -> Result<FieldLocation, String> {
+        let kind = match self.field_kind(&change.field) {
+            Some(v) => v,
+            None => return Err(format!("{} is not an editable field", change.field))
+        };
+        let location = match self.locate(&change.ids, &change.field) {
+            Some(v) => v,
+            None => return Err(format!("{} of {:?} is not a field of a {} case", change.field, change.ids, self.id()))
+        };
+        match (&change.value, &change.operations, kind) {
+            (Some(value), None, kind) => validate_value(&change.field, kind, value)?,
+            (None, Some(_), FieldKind::Text) => (),
+            (None, Some(_), _) => return Err(format!("{} is not a text field", change.field)),
+            _ => return Err("a change needs either a value or operations".into())
+        };
+        Ok(location)
+    }
+}
+
+// reads a case as the type a framework lays its cases out with, and writes it back the way the type has it.
+fn parse_as<T: Serialize + DeserializeOwned>(framework: &str, case: Document) -> Result<Document, String> {
+    let parsed = bson::from_document::<T>(case).map_err(|e| e.to_string())?;
+    let mut case = bson::to_document(&parsed).map_err(|e| e.to_string())?;
+    case.insert("framework", framework);
+    Ok(case)
+}
+
+// reads a case as the type a framework lays its cases out with.
+fn read_as<T: DeserializeOwned>(framework: &str, case: &Document) -> Result<T, String> {
+    bson::from_document::<T>(case.clone()).map_err(|e| format!("not a valid {} case: {}", framework, e))
+}
+
+// how many documents a field can list.
+pub const MAX_DOCUMENTS: usize = 50;
+
+// checks that a value fits the kind of field it is set on.
+fn validate_value(field: &str, kind: FieldKind, value: &TextOrIntValue) -> Result<(), String> {
+    match (kind, value) {
+        (FieldKind::Text, TextOrIntValue::String(text)) => {
+            if text.chars().count() > MAX_TEXT_LENGTH {
+                return Err(format!("{} can be at most {} characters", field, MAX_TEXT_LENGTH));
+            }
+        },
+        (FieldKind::Choice, TextOrIntValue::String(_)) | (FieldKind::Score, TextOrIntValue::Number(_)) => (),
+        (FieldKind::Documents, TextOrIntValue::Documentation(documents)) => validate_documents(field, documents)?,
+        (FieldKind::Text | FieldKind::Choice, _) => return Err(format!("{} must be a text", field)),
+        (FieldKind::Score, _) => return Err(format!("{} must be a number", field)),
+        (FieldKind::Documents, _) => return Err(format!("{} must be a list of documents", field))
+    };
+    Ok(())
+}
+
+// checks that every document is named and that there aren't too many of them.
+fn validate_documents(field: &str, documents: &[Documentation]) -> Result<(), String> {
+    if documents.len() > MAX_DOCUMENTS {
+        return Err(format!("{} can list at most {} documents", field, MAX_DOCUMENTS));
+    }
+    if documents.iter().any(|v| v.name.trim().is_empty()) {
+        return Err(format!("every document in {} needs a name", field));
+    }
+    if documents.iter().any(|v| v.name.chars().count() > MAX_TEXT_LENGTH || v.src.chars().count() > MAX_TEXT_LENGTH) {
+        return Err(format!("the documents in {} can have at most {} characters in a name or source", field, MAX_TEXT_LENGTH));
+    }
+    Ok(())
+}
+
+// checks that a value is one of those the template of the case lists for the field. a field the template lists no
+// values for takes any value of its kind, as templates from before fields had their values listed don't.
+pub fn check_allowed(case: &Document, field: &str, value: &TextOrIntValue) -> Result<(), String> {
+    let values = match case.get_document("field_values").ok().and_then(|v| v.get(field)) {
+        Some(v) => match bson::from_bson::<FieldValues>(v.clone()) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("the values listed for {} are not valid: {}", field, e))
+        },
+        None => return Ok(())
+    };
+    match (values, value) {
+        (FieldValues::Choices(choices), TextOrIntValue::String(choice)) => {
+            if !choices.contains(choice) {
+                return Err(format!("{} is not a valid {}, expected one of {:?}", choice, field, choices));
+            }
+        },
+        (FieldValues::Range { min, max }, TextOrIntValue::Number(score)) => {
+            if !(min..=max).contains(score) {
+                return Err(format!("{} must be between {} and {}", field, min, max));
+            }
+        },
+        (values, _) => return Err(format!("{} can only be one of {:?}", field, values))
+    };
+    Ok(())
+}
+
+// the frameworks cases can be created for, registered at startup.
+#[derive(Default)]
+pub struct FrameworkRegistry {
+    frameworks: HashMap<&'static str, Box<dyn Framework>>
+}
+
+impl FrameworkRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, framework: Box<dyn Framework>) {
+        self.frameworks.insert(framework.id(), framework);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&dyn Framework> {
+        self.frameworks.get(id).map(|v| v.as_ref())
+    }
+}
+
+// how many of the largest gaps a score lists.
+const LARGEST_GAPS: usize = 10;
+
+// the average of the values, None if there are none.
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    match count {
+        0 => None,
+        n => Some(sum / n as f64)
+    }
+}
+
+// how far the target is from the current score, None unless both are known.
+fn gap(current: Option<f64>, target: Option<f64>) -> Option<f64> {
+    Some(target? - current?)
+}
+
+// the items falling short of their target the most, largest first.
+fn largest_gaps(mut gaps: Vec<ItemGap>) -> Vec<ItemGap> {
+    gaps.retain(|v| v.gap > 0.0);
+    gaps.sort_by(|a, b| b.gap.total_cmp(&a.gap));
+    gaps.truncate(LARGEST_GAPS);
+    gaps
+}
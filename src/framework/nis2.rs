@@ -0,0 +1,150 @@
+use mongodb::bson::{self, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::framework::{average, parse_as, read_as, Framework};
+use crate::types::case_database::Documentation;
+use crate::types::crosswalk::ProjectedItem;
+use crate::types::framework::{CaseScore, ControlScore, FieldKind, FieldLocation, FieldValues};
+
+
+// the cybersecurity risk-management measures of article 21 of the nis2 directive.
+// measures have no subcontrols, fields are found through the id of their measure alone.
+pub struct NIS2Framework;
+
+impl Framework for NIS2Framework {
+    fn id(&self) -> &'static str {
+        "nis2"
+    }
+
+    fn parse_case(&self, case: Document) -> Result<Document, String> {
+        parse_as::<NIS2Case>(self.id(), case)
+    }
+
+    fn field_kind(&self, field: &str) -> Option<FieldKind> {
+        NIS2Field::parse(field).map(|v| v.kind())
+    }
+
+    fn locate(&self, ids: &[String], field: &str) -> Option<FieldLocation> {
+        match ids {
+            [measure_id] => Some(location(measure_id, field)),
+            _ => None
+        }
+    }
+
+    // the share of the applicable measures that are in place, a measure in progress counting as half.
+    fn score(&self, case: &Document) -> Result<CaseScore, String> {
+        let case = read_as::<NIS2Case>(self.id(), case)?;
+        let progress = |status: &str| match status {
+            "implemented" => Some(1.0),
+            "in_progress" => Some(0.5),
+            "not_applicable" => None,
+            _ => Some(0.0)
+        };
+
+        let controls = case.measures.iter()
+            .map(|measure| ControlScore {
+                id: measure.id.to_string(),
+                title: measure.title.to_string(),
+                current: progress(&measure.status),
+                target: None,
+                gap: None
+            })
+            .collect();
+
+        Ok(CaseScore {
+            max: 1.0,
+            current: average(case.measures.iter().filter_map(|measure| progress(&measure.status))),
+            target: None,
+            gap: None,
+            coverage: None,
+            controls,
+            largest_gaps: Vec::new()
+        })
+    }
+
+    // measures take their status, notes and evidence from what is projected onto them.
+    fn seed(&self, case: &mut Document, items: &[ProjectedItem]) -> Result<(), String> {
+        let mut measures = match case.get("measures").cloned().map(bson::from_bson::<Vec<NIS2Measure>>) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => return Err(format!("not a valid nis2 template: {}", e)),
+            None => return Err("not a valid nis2 template: missing measures".into())
+        };
+        for measure in measures.iter_mut() {
+            if let Some(item) = items.iter().find(|v| v.id == measure.id) {
+                measure.status = if item.not_applicable { "not_applicable".into() } else { item.status().into() };
+                measure.notes = item.observation.to_string();
+                measure.evidence = item.evidence.clone();
+            }
+        }
+        case.insert("measures", bson::to_bson(&measures).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+}
+
+// where a measure field sits in a nis2 case.
+pub fn location(measure_id: &str, field: &str) -> FieldLocation {
+    FieldLocation {
+        arrays: vec![("measures", measure_id.to_string())],
+        field: field.to_string()
+    }
+}
+
+// the measure fields clients are allowed to edit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NIS2Field {
+    Status,
+    Evidence,
+    Owner,
+    Notes
+}
+
+impl NIS2Field {
+    pub fn parse(field: &str) -> Option<Self> {
+        match field {
+            "status" => Some(Self::Status),
+            "evidence" => Some(Self::Evidence),
+            "owner" => Some(Self::Owner),
+            "notes" => Some(Self::Notes),
+            _ => None
+        }
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Self::Status => FieldKind::Choice,
+            Self::Evidence => FieldKind::Documents,
+            Self::Owner | Self::Notes => FieldKind::Text
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NIS2Case {
+    pub case_id: String,
+    pub name: String,
+    pub group_id: String,
+    #[serde(default)]
+    pub revision: i64,
+    // the version and locale of the template the case was created from, unknown for cases from before template versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_version: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_locale: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub field_values: HashMap<String, FieldValues>,
+    #[serde(default)]
+    pub measures: Vec<NIS2Measure>
+}
+
+// a cybersecurity risk-management measure from article 21 of the nis2 directive.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NIS2Measure {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub status: String,
+    pub evidence: Vec<Documentation>,
+    pub owner: String,
+    pub notes: String
+}
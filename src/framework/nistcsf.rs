@@ -0,0 +1,211 @@
+use mongodb::bson::{self, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::framework::{average, gap, largest_gaps, parse_as, read_as, Framework};
+use crate::types::crosswalk::ProjectedItem;
+use crate::types::framework::{CaseScore, ControlScore, FieldKind, FieldLocation, FieldValues, ItemGap};
+
+
+// the csf implementation tiers, from partial to adaptive. a tier of 0 means not assessed yet.
+pub const MIN_TIER: i32 = 0;
+pub const MAX_TIER: i32 = 4;
+
+// the nist cybersecurity framework 2.0, assessed per subcategory on current and target tiers.
+// subcategory ids carry their function and category, e.g. GV.OC-01 belongs to category GV.OC of function GV.
+pub struct NISTCSFFramework;
+
+impl Framework for NISTCSFFramework {
+    fn id(&self) -> &'static str {
+        "nistcsf"
+    }
+
+    fn parse_case(&self, case: Document) -> Result<Document, String> {
+        parse_as::<NISTCSFCase>(self.id(), case)
+    }
+
+    fn field_kind(&self, field: &str) -> Option<FieldKind> {
+        NISTCSFField::parse(field).map(|v| v.kind())
+    }
+
+    fn locate(&self, ids: &[String], field: &str) -> Option<FieldLocation> {
+        match ids {
+            [subcategory_id] => location(subcategory_id, field),
+            _ => None
+        }
+    }
+
+    // averages the tiers of the assessed subcategories, per function and overall.
+    fn score(&self, case: &Document) -> Result<CaseScore, String> {
+        let case = read_as::<NISTCSFCase>(self.id(), case)?;
+        let current = |subcategory: &NISTCSFSubcategory| Some(subcategory.current_tier as f64).filter(|v| *v > 0.0);
+        let target = |subcategory: &NISTCSFSubcategory| Some(subcategory.target_tier as f64).filter(|v| *v > 0.0);
+
+        let controls = case.functions.iter()
+            .map(|function| {
+                let current = average(subcategories(function).filter_map(current));
+                let target = average(subcategories(function).filter_map(target));
+                ControlScore { id: function.id.to_string(), title: function.title.to_string(), current, target, gap: gap(current, target) }
+            })
+            .collect();
+        let gaps = case.functions.iter()
+            .flat_map(|function| subcategories(function).map(move |subcategory| (function, subcategory)))
+            .filter_map(|(function, subcategory)| Some(ItemGap {
+                control_id: function.id.to_string(),
+                subcontrol_id: Some(subcategory.id.to_string()),
+                title: subcategory.description.to_string(),
+                current: current(subcategory)?,
+                target: target(subcategory)?,
+                gap: (subcategory.target_tier - subcategory.current_tier) as f64
+            }))
+            .collect();
+
+        let current = average(case.functions.iter().flat_map(subcategories).filter_map(current));
+        let target = average(case.functions.iter().flat_map(subcategories).filter_map(target));
+        Ok(CaseScore {
+            max: MAX_TIER as f64,
+            current,
+            target,
+            gap: gap(current, target),
+            coverage: None,
+            controls,
+            largest_gaps: largest_gaps(gaps)
+        })
+    }
+
+    // subcategories take their observation and plan from what is projected onto them, and their tiers from the
+    // projected scores put on the tier scale. a subcategory is only left unassessed if nothing projected onto it was.
+    fn seed(&self, case: &mut Document, items: &[ProjectedItem]) -> Result<(), String> {
+        let mut functions = match case.get("functions").cloned().map(bson::from_bson::<Vec<NISTCSFFunction>>) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => return Err(format!("not a valid nistcsf template: {}", e)),
+            None => return Err("not a valid nistcsf template: missing functions".into())
+        };
+        for subcategory in functions.iter_mut().flat_map(|function| function.categories.iter_mut()).flat_map(|category| category.subcategories.iter_mut()) {
+            if let Some(item) = items.iter().find(|v| v.id == subcategory.id) {
+                subcategory.observation = item.observation.to_string();
+                subcategory.current_tier = tier(item.current);
+                subcategory.plan = item.plan.to_string();
+                subcategory.target_tier = tier(item.target);
+            }
+        }
+        case.insert("functions", bson::to_bson(&functions).map_err(|e| e.to_string())?);
+        Ok(())
+    }
+}
+
+// puts a share of the highest score onto the tiers. assessed shares, 0 included, go from the first tier up to the
+// highest, as tier 0 is kept for what hasn't been assessed.
+fn tier(share: Option<f64>) -> i32 {
+    match share {
+        Some(v) => 1 + (v.clamp(0.0, 1.0) * (MAX_TIER - 1) as f64).round() as i32,
+        None => MIN_TIER
+    }
+}
+
+fn subcategories(function: &NISTCSFFunction) -> impl Iterator<Item = &NISTCSFSubcategory> {
+    function.categories.iter().flat_map(|category| category.subcategories.iter())
+}
+
+// where a subcategory field sits in a nist csf case, None if the id doesn't name a function and category.
+pub fn location(subcategory_id: &str, field: &str) -> Option<FieldLocation> {
+    let (category_id, _) = subcategory_id.split_once('-')?;
+    let (function_id, _) = category_id.split_once('.')?;
+    Some(FieldLocation {
+        arrays: vec![
+            ("functions", function_id.to_string()),
+            ("categories", category_id.to_string()),
+            ("subcategories", subcategory_id.to_string())
+        ],
+        field: field.to_string()
+    })
+}
+
+// the subcategory fields clients are allowed to edit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NISTCSFField {
+    Observation,
+    CurrentTier,
+    Plan,
+    TargetTier
+}
+
+impl NISTCSFField {
+    pub fn parse(field: &str) -> Option<Self> {
+        match field {
+            "observation" => Some(Self::Observation),
+            "current_tier" => Some(Self::CurrentTier),
+            "plan" => Some(Self::Plan),
+            "target_tier" => Some(Self::TargetTier),
+            _ => None
+        }
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Self::Observation | Self::Plan => FieldKind::Text,
+            Self::CurrentTier | Self::TargetTier => FieldKind::Score
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NISTCSFCase {
+    pub case_id: String,
+    pub name: String,
+    pub group_id: String,
+    #[serde(default)]
+    pub revision: i64,
+    // the version and locale of the template the case was created from, unknown for cases from before template versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_version: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_locale: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub field_values: HashMap<String, FieldValues>,
+    pub functions: Vec<NISTCSFFunction>
+}
+
+// govern, identify, protect, detect, respond and recover.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NISTCSFFunction {
+    pub id: String,
+    pub title: String,
+    pub categories: Vec<NISTCSFCategory>
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NISTCSFCategory {
+    pub id: String,
+    pub title: String,
+    pub subcategories: Vec<NISTCSFSubcategory>
+}
+
+// a subcategory assessed on the csf implementation tiers, 0 meaning it has not been assessed yet.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NISTCSFSubcategory {
+    pub id: String,
+    pub description: String,
+    pub observation: String,
+    pub current_tier: i32,
+    pub plan: String,
+    pub target_tier: i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assessed_shares_are_put_on_the_assessed_tiers() {
+        assert_eq!(tier(Some(0.0)), 1);
+        assert_eq!(tier(Some(0.3)), 2);
+        assert_eq!(tier(Some(0.5)), 3);
+        assert_eq!(tier(Some(1.0)), MAX_TIER);
+    }
+
+    #[test]
+    fn only_unassessed_subcategories_are_left_on_tier_0() {
+        assert_eq!(tier(None), MIN_TIER);
+    }
+}
@@ -4,17 +4,34 @@ pub mod types;
 pub mod api;
 pub mod database;
 pub mod service;
+pub mod framework;
 
 use std::env;
+use database::action::ActionDatabase;
+use database::audit::AuditDatabase;
 use database::case::CaseDatabase;
+use database::comment::CommentDatabase;
+use database::role::RoleDatabase;
+use database::template::TemplateDatabase;
 use rocket::Config;
 
+use api::action_handler;
 use api::case_handler;
 use api::collaboration_handler;
+use api::comment_handler;
+use api::role_handler;
+use api::template_handler;
 use api::middleware_handler::Logger;
 use api::cors::{CORS, all_options};
 use service::user::UserService;
 use service::socket::SocketService;
+use service::ticket::TicketService;
+use framework::FrameworkRegistry;
+use framework::crosswalk::CrosswalkRegistry;
+use framework::cis18::CIS18Framework;
+use framework::nis2::NIS2Framework;
+use framework::iso27001::ISO27001Framework;
+use framework::nistcsf::NISTCSFFramework;
 
 
 #[rocket::main]
@@ -32,25 +49,80 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .merge(("port", port))
         .merge(("address", "0.0.0.0"));
 
+    let database = database::connect().await;
+
+    let mut frameworks = FrameworkRegistry::new();
+    frameworks.register(Box::new(CIS18Framework));
+    frameworks.register(Box::new(NIS2Framework));
+    frameworks.register(Box::new(ISO27001Framework));
+    frameworks.register(Box::new(NISTCSFFramework));
+
+    // the crosswalks between frameworks are read from data files, by default the ones shipped with the service
+    let crosswalk_directory = env::var("CROSSWALK_DIRECTORY").unwrap_or_else(|_| "data/crosswalks".into());
+    let crosswalks = CrosswalkRegistry::load(&crosswalk_directory)
+        .map_err(|e| format!("error loading crosswalks from {}: {}", crosswalk_directory, e))?;
+
+    // so are the templates cases are created from, which are stored unless the database has their version already
+    let templates = TemplateDatabase::new(&database).await;
+    let template_directory = env::var("TEMPLATE_DIRECTORY").unwrap_or_else(|_| "data/templates".into());
+    templates.seed_templates(&frameworks, &template_directory).await
+        .map_err(|e| format!("error storing templates from {}: {}", template_directory, e))?;
+
     rocket::build()
     .configure(figment)
     .manage(UserService::new())
     .manage(SocketService::new())
-    .manage(CaseDatabase::new().await)
+    .manage(TicketService::new())
+    .manage(CaseDatabase::new(&database).await)
+    .manage(AuditDatabase::new(&database).await)
+    .manage(templates)
+    .manage(ActionDatabase::new(&database).await)
+    .manage(CommentDatabase::new(&database).await)
+    .manage(RoleDatabase::new(&database).await)
+    .manage(frameworks)
+    .manage(crosswalks)
     .manage(reqwest::Client::new())
     .attach(CORS)
     .attach(Logger)
     .mount("/", routes![
 
+        collaboration_handler::create_ticket,
         collaboration_handler::connect,
 
-        case_handler::create_cis18_case,
+        case_handler::create_case,
         case_handler::rename_case,
+        case_handler::change_implementation_group,
+        case_handler::migrate_case,
+        case_handler::seed_case,
         case_handler::delete_case,
+        case_handler::revert_case,
 
         case_handler::get_case,
         case_handler::get_cases,
+        case_handler::get_case_score,
+        case_handler::get_case_history,
         case_handler::export_case_docx,
+        case_handler::get_case_projection,
+
+        template_handler::get_templates,
+        template_handler::get_template,
+        template_handler::create_template,
+        template_handler::update_template,
+        template_handler::delete_template,
+
+        action_handler::create_action,
+        action_handler::get_case_actions,
+        action_handler::get_group_actions,
+        action_handler::update_action,
+        action_handler::delete_action,
+
+        comment_handler::create_comment,
+        comment_handler::get_comments,
+        comment_handler::delete_comment,
+
+        role_handler::get_roles,
+        role_handler::set_role,
+        role_handler::delete_role,
 
         all_options
     ])
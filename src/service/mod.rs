@@ -1,3 +1,6 @@
 pub mod socket;
+pub mod text;
+pub mod ticket;
 pub mod token;
-pub mod user;
\ No newline at end of file
+pub mod user;
+pub mod verifier;
\ No newline at end of file
@@ -1,88 +1,273 @@
 use std::sync::Arc;
-use rocket::{futures::stream::SplitSink, tokio::sync::RwLock};
+use std::env;
+use rocket::futures::SinkExt;
+use rocket::{futures::stream::SplitSink, tokio::sync::{Mutex, RwLock}};
 use rocket_ws::{stream::DuplexStream, Message};
+use rocket_ws::frame::{CloseCode, CloseFrame};
+use serde::Serialize;
 use std::collections::HashMap;
+use uuid::Uuid;
 
+use crate::types::collaboration_handler::{Collaborator, Focus, Presence};
+
+
+type Sender = Arc<Mutex<SplitSink<DuplexStream, Message>>>;
+
+pub struct Client {
+    pub user_id: String,
+    pub case_id: String,
+    pub focus: Option<Focus>,
+    sender: Sender
+}
+
+impl Client {
+    fn collaborator(&self, connection_id: &str) -> Collaborator {
+        Collaborator {
+            connection_id: connection_id.to_string(),
+            user_id: self.user_id.to_string(),
+            focus: self.focus.clone()
+        }
+    }
+}
 
 pub struct SocketService {
-    cases: Arc<RwLock<HashMap<String, Vec<String>>>>, // users for an individual case
-    clients: Arc<RwLock<HashMap<String, SplitSink<DuplexStream, Message>>>> // each client's connection
+    cases: Arc<RwLock<HashMap<String, Vec<String>>>>, // connections for an individual case
+    clients: Arc<RwLock<HashMap<String, Client>>>, // each client's connection, keyed by connection id
+    max_clients_per_case: usize
 }
 
 impl SocketService {
 
     pub fn new() -> Self {
+        let max_clients_per_case = match env::var("MAX_CLIENTS_PER_CASE") {
+            Ok(v) => v.parse().expect("MAX_CLIENTS_PER_CASE must be a valid number"),
+            Err(_) => 10
+        };
         Self {
             cases: Arc::new(RwLock::new(HashMap::new())),
-            clients: Arc::new(RwLock::new(HashMap::new()))
+            clients: Arc::new(RwLock::new(HashMap::new())),
+            max_clients_per_case
         }
     }
 
-    pub async fn active_client(&self, case_id: &String) -> bool {
+    // registers a connection for the case and returns its connection id. the first message on the connection is the
+    // snapshot of the case, the connection is held until it is sent so no broadcast gets ahead of it.
+    // if the case already has the maximum number of connections, the connection is closed with a reason instead of
+    // getting the snapshot, and None is returned.
+    pub async fn add_client(&self, case_id: &String, user_id: &String, sender: SplitSink<DuplexStream, Message>, snapshot: String) -> Option<String> {
+        let connection_id = Uuid::new_v4().to_string();
+        let sender: Sender = Arc::new(Mutex::new(sender));
+        let mut first = sender.lock().await;
+        let joined = {
+            let mut cases = self.cases.write().await;
+            let case_list = cases.entry(case_id.to_string()).or_default();
+
+            if case_list.len() >= self.max_clients_per_case {
+                None
+            } else {
+                case_list.push(connection_id.clone());
+
+                let client = Client {
+                    user_id: user_id.to_string(),
+                    case_id: case_id.to_string(),
+                    focus: None,
+                    sender: sender.clone()
+                };
+                let joined = client.collaborator(&connection_id);
+                self.clients.write().await.insert(connection_id.clone(), client);
+                Some(joined)
+            }
+        };
+
+        // the connection is written to once the lock is released, so a slow client doesn't hold up every other case
+        let joined = match joined {
+            Some(v) => v,
+            None => {
+                let reason = format!("case already has the maximum of {} connected editors", self.max_clients_per_case);
+                println!("refused client {} on case {}: {}", user_id, case_id, reason);
+                let frame = CloseFrame { code: CloseCode::Again, reason: reason.into() };
+                if let Err(e) = first.send(Message::Close(Some(frame))).await {
+                    eprintln!("error closing refused connection: {}", e);
+                }
+                return None;
+            }
+        };
+        let sent = first.send(Message::Text(snapshot)).await;
+        drop(first);
+        if let Err(e) = sent {
+            eprintln!("error sending case snapshot: {}", e);
+            self.unregister(&connection_id).await;
+            return None;
+        }
+
+        // tell the new client who is already here, and everyone else that the client joined
+        let others = self.collaborators(case_id).await.into_iter()
+            .filter(|collaborator| collaborator.connection_id != connection_id)
+            .collect();
+        if let Err(e) = self.send(&connection_id, &Presence::PresenceSnapshot(others)).await {
+            eprintln!("error sending presence snapshot: {}", e);
+        }
+        if let Err(e) = self.broadcast(case_id, Some(&connection_id), &Presence::UserJoined(joined)).await {
+            eprintln!("error broadcasting joined client: {}", e);
+        }
+        Some(connection_id)
+    }
+
+    // lists everyone connected to the case, along with what they are currently editing.
+    pub async fn collaborators(&self, case_id: &String) -> Vec<Collaborator> {
         let cases = self.cases.read().await;
-        cases.contains_key(case_id)
+        let clients = self.clients.read().await;
+        match cases.get(case_id) {
+            Some(connection_ids) => connection_ids.iter()
+                .filter_map(|id| clients.get(id).map(|client| client.collaborator(id)))
+                .collect(),
+            None => Vec::new()
+        }
     }
 
-    // stores a client's connection info (user_id, socket).
-    pub async fn add_client(&self, user_id: &String, sender: SplitSink<DuplexStream, Message>) {
-        let mut clients = self.clients.write().await;
-        clients.insert(user_id.to_string(), sender);
+    // updates the field a connection is editing and lets the rest of the case know.
+    pub async fn set_focus(&self, connection_id: &String, focus: Option<Focus>) -> Result<(), serde_json::Error> {
+        let (case_id, collaborator) = {
+            let mut clients = self.clients.write().await;
+            match clients.get_mut(connection_id) {
+                Some(client) => {
+                    client.focus = focus;
+                    (client.case_id.to_string(), client.collaborator(connection_id))
+                },
+                None => return Ok(())
+            }
+        };
+        self.broadcast(&case_id, Some(connection_id), &Presence::Focus(collaborator)).await
     }
 
-    // checks if a case list already exists, otherwise creates one.
-    pub async fn add_client_to_case_list(&self, case_id: &String, user_id: &String) {
-        let mut cases = self.cases.write().await;
-        if let Some(case_list) = cases.get_mut(case_id) {
-            case_list.push(user_id.to_string())
-        } else {
-            cases.insert(case_id.to_string(), vec![user_id.to_string()]);
+    // sends a message to a single connection.
+    pub async fn send<T: Serialize>(&self, connection_id: &String, message: &T) -> Result<(), Box<dyn std::error::Error>> {
+        let payload = serde_json::to_string(message)?;
+        let sender = match self.clients.read().await.get(connection_id) {
+            Some(client) => client.sender.clone(),
+            None => return Err(format!("connection {} was not found", connection_id).into())
+        };
+        let mut sender = sender.lock().await;
+        sender.send(Message::Text(payload)).await?;
+        Ok(())
+    }
+
+    // sends a message to every connection on the case, except the one it originated from if any.
+    // connections that fail are assumed gone, they are removed from the registry and announced as having left.
+    pub async fn broadcast<T: Serialize>(&self, case_id: &String, connection_id: Option<&String>, message: &T) -> Result<(), serde_json::Error> {
+        let payload = serde_json::to_string(message)?;
+        let mut failed = self.send_to_case(case_id, connection_id, &payload).await;
+
+        while let Some(id) = failed.pop() {
+            if let Some((_, left)) = self.unregister(&id).await {
+                let payload = serde_json::to_string(&Presence::UserLeft(left))?;
+                failed.extend(self.send_to_case(case_id, None, &payload).await);
+            }
+        }
+        Ok(())
+    }
+
+    // cleans up after a connection and lets the rest of the case know the client left.
+    pub async fn remove_client(&self, connection_id: &String) {
+        if let Some((case_id, left)) = self.unregister(connection_id).await {
+            if let Err(e) = self.broadcast(&case_id, Some(connection_id), &Presence::UserLeft(left)).await {
+                eprintln!("error broadcasting client leaving: {}", e);
+            }
+        }
+    }
+
+    // closes every connection on the case with a reason and forgets about them, e.g. once the case is deleted.
+    pub async fn close_case(&self, case_id: &String, reason: &str) {
+        let connection_ids = self.cases.read().await.get(case_id).cloned().unwrap_or_default();
+        for connection_id in connection_ids {
+            let sender = self.clients.read().await.get(&connection_id).map(|client| client.sender.clone());
+            if let Some(sender) = sender {
+                let frame = CloseFrame { code: CloseCode::Normal, reason: reason.to_string().into() };
+                if let Err(e) = sender.lock().await.send(Message::Close(Some(frame))).await {
+                    eprintln!("error closing connection {}: {}", connection_id, e);
+                }
+            }
+            self.unregister(&connection_id).await;
+        }
+    }
+
+    // sends the payload to the connections on a case, returning the ids of the connections that failed.
+    async fn send_to_case(&self, case_id: &String, exclude: Option<&String>, payload: &str) -> Vec<String> {
+
+        // collect the receivers first, so the registry isn't locked while sending.
+        let receivers: Vec<(String, Sender)> = {
+            let cases = self.cases.read().await;
+            let clients = self.clients.read().await;
+            match cases.get(case_id) {
+                Some(connection_ids) => connection_ids.iter()
+                    .filter(|id| Some(*id) != exclude)
+                    .filter_map(|id| clients.get(id).map(|client| (id.to_string(), client.sender.clone())))
+                    .collect(),
+                None => Vec::new()
+            }
+        };
+
+        let mut failed = Vec::new();
+        for (id, sender) in receivers {
+            let mut sender = sender.lock().await;
+            if let Err(e) = sender.send(Message::Text(payload.to_string())).await {
+                eprintln!("error sending message to connection {}: {}", id, e);
+                failed.push(id);
+            }
         }
+        failed
     }
 
-    // cleans up after a client. this includes removing their connection details and from their associated case.
-    pub async fn remove_client(&self, user_id: String, case_id: String) {
+    // removes a connection's details and takes it off its associated case, returning the case and who left.
+    async fn unregister(&self, connection_id: &String) -> Option<(String, Collaborator)> {
 
         // remove client from clients (curly brackets is some kind of scoping, minimizing the lock on the hashmap?)
-        {
+        let client = {
             let mut clients = self.clients.write().await;
-            if clients.remove(&user_id).is_some() {
-                println!("removed client {} from clients", user_id);
-            } else {
-                println!("client {} was not found", user_id);
+            match clients.remove(connection_id) {
+                Some(client) => client,
+                None => {
+                    println!("connection {} was not found", connection_id);
+                    return None;
+                }
             }
-        }
+        };
+        println!("removed connection {} of client {} from clients", connection_id, client.user_id);
 
-        // remove user_id from cases
+        // remove connection_id from cases
         let mut cases = self.cases.write().await;
-        if let Some(case) = cases.get_mut(&case_id) {
-            case.retain(|id| id != &user_id);
-            println!("removed client {} from case {}", user_id, case_id);
+        if let Some(case) = cases.get_mut(&client.case_id) {
+            case.retain(|id| id != connection_id);
+            println!("removed connection {} from case {}", connection_id, client.case_id);
             // if case list is empty afterwards, delete case
             if case.is_empty() {
-                cases.remove(&case_id);
+                cases.remove(&client.case_id);
                 println!("case list is empty and has been removed");
             }
         } else {
-            println!("case {} was not found", case_id);
+            println!("case {} was not found", client.case_id);
         }
+
+        let left = client.collaborator(connection_id);
+        Some((client.case_id, left))
     }
 
     pub async fn print_clients(&self) {
         let clients = self.clients.read().await;
         println!("connected clients: ");
-        for user_id in clients.keys() {
-            println!("user_id: {}", user_id);
+        for (connection_id, client) in clients.iter() {
+            println!("connection_id: {}, user_id: {}, case_id: {}", connection_id, client.user_id, client.case_id);
         }
     }
 
     pub async fn print_case_lists(&self) {
         let cases = self.cases.read().await;
-        for (case_id, user_id_list) in cases.iter() {
+        for (case_id, connection_id_list) in cases.iter() {
             println!("case_id: {}", case_id);
-            for user_id in user_id_list {
-                println!("user_id: {}", user_id);
+            for connection_id in connection_id_list {
+                println!("connection_id: {}", connection_id);
             }
         }
     }
 
-}
\ No newline at end of file
+}
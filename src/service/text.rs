@@ -0,0 +1,263 @@
+use crate::types::collaboration_handler::TextOperation;
+
+
+// operational transform for concurrent edits to the same text field.
+// positions and lengths are counted in utf-16 code units, the same way javascript clients count them.
+// the operations of a single edit are applied in order, each against the result of the one before it.
+
+
+// transforms the operations of an edit so they apply on top of operations that were applied before them.
+// when both insert at the same position, the earlier insert ends up first. None if a position overflows, which
+// operations that passed check can't do.
+pub fn transform(ops: &[TextOperation], against: &[TextOperation]) -> Option<Vec<TextOperation>> {
+    Some(transform_both(ops, against)?.0)
+}
+
+// checks that operations stay inside of a text of the given length, before they are transformed or applied.
+pub fn check(length: usize, ops: &[TextOperation]) -> Result<(), String> {
+    let mut length = length;
+    for op in ops {
+        match op {
+            TextOperation::Insert { position, text } => {
+                if *position > length {
+                    return Err(format!("insert at {} is outside of the text", position));
+                }
+                length = length.checked_add(len(text)).ok_or("insert makes the text too long")?;
+            },
+            TextOperation::Delete { position, length: deleted } => {
+                if position.checked_add(*deleted).is_none_or(|end| end > length) {
+                    return Err(format!("delete of {} at {} is outside of the text", deleted, position));
+                }
+                length -= deleted;
+            }
+        }
+    }
+    Ok(())
+}
+
+// the length a text had before operations were applied to it, None if they can't have been.
+pub fn length_before(text: &str, applied: &[TextOperation]) -> Option<usize> {
+    applied.iter().rev().try_fold(len(text), |length, op| match op {
+        TextOperation::Insert { text, .. } => length.checked_sub(len(text)),
+        TextOperation::Delete { length: deleted, .. } => length.checked_add(*deleted)
+    })
+}
+
+// applies operations to a text, failing if an operation falls outside of it or splits a character.
+pub fn apply(text: &str, ops: &[TextOperation]) -> Result<String, String> {
+    let mut units: Vec<u16> = text.encode_utf16().collect();
+    for op in ops {
+        match op {
+            TextOperation::Insert { position, text } => {
+                if *position > units.len() {
+                    return Err(format!("insert at {} is outside of the text", position));
+                }
+                units.splice(position..position, text.encode_utf16());
+            },
+            TextOperation::Delete { position, length } => {
+                let end = match position.checked_add(*length) {
+                    Some(v) if v <= units.len() => v,
+                    _ => return Err(format!("delete of {} at {} is outside of the text", length, position))
+                };
+                units.drain(*position..end);
+            }
+        }
+    }
+    String::from_utf16(&units).map_err(|_| "edit splits a character".to_string())
+}
+
+// transforms two sequences of operations against each other, the second being the one applied first.
+// returns the first transformed to apply after the second, and the second transformed to apply after the first.
+fn transform_both(a: &[TextOperation], b: &[TextOperation]) -> Option<(Vec<TextOperation>, Vec<TextOperation>)> {
+    match (a, b) {
+        ([], _) => Some((Vec::new(), b.to_vec())),
+        (_, []) => Some((a.to_vec(), Vec::new())),
+        ([x], [y]) => transform_pair(x, y),
+        ([first, rest @ ..], _) if !rest.is_empty() => {
+            let (first, b) = transform_both(std::slice::from_ref(first), b)?;
+            let (mut rest, b) = transform_both(rest, &b)?;
+            let mut ops = first;
+            ops.append(&mut rest);
+            Some((ops, b))
+        },
+        (_, [first, rest @ ..]) => {
+            let (a, first) = transform_both(a, std::slice::from_ref(first))?;
+            let (a, mut rest) = transform_both(&a, rest)?;
+            let mut ops = first;
+            ops.append(&mut rest);
+            Some((a, ops))
+        }
+    }
+}
+
+fn transform_pair(x: &TextOperation, y: &TextOperation) -> Option<(Vec<TextOperation>, Vec<TextOperation>)> {
+    use TextOperation::{Delete, Insert};
+
+    let pair = match (x, y) {
+        (Insert { position: p, text: s }, Insert { position: q, text: t }) => {
+            if q <= p {
+                (vec![insert(p.checked_add(len(t))?, s)], vec![y.clone()])
+            } else {
+                (vec![x.clone()], vec![insert(q.checked_add(len(s))?, t)])
+            }
+        },
+        (Insert { position: p, text: s }, Delete { position: q, length: m }) => {
+            let end = q.checked_add(*m)?;
+            if p <= q {
+                (vec![x.clone()], vec![delete(q.checked_add(len(s))?, *m)])
+            } else if *p >= end {
+                (vec![insert(p - m, s)], vec![y.clone()])
+            } else {
+                // the insert lands inside the deleted range, keep the inserted text and delete around it
+                (vec![insert(*q, s)], without_empty(vec![delete(*q, p - q), delete(q.checked_add(len(s))?, end - p)]))
+            }
+        },
+        (Delete { .. }, Insert { .. }) => {
+            let (y, x) = transform_pair(y, x)?;
+            (x, y)
+        },
+        (Delete { position: p, length: l }, Delete { position: q, length: m }) => {
+            let overlap = p.checked_add(*l)?.min(q.checked_add(*m)?).saturating_sub(*p.max(q));
+            (
+                without_empty(vec![delete(shift_delete(*p, *q, *m)?, l - overlap)]),
+                without_empty(vec![delete(shift_delete(*q, *p, *l)?, m - overlap)])
+            )
+        }
+    };
+    Some(pair)
+}
+
+// where a position ends up after a range has been deleted.
+fn shift_delete(position: usize, deleted: usize, length: usize) -> Option<usize> {
+    if position <= deleted {
+        Some(position)
+    } else if position < deleted.checked_add(length)? {
+        Some(deleted)
+    } else {
+        Some(position - length)
+    }
+}
+
+fn insert(position: usize, text: &str) -> TextOperation {
+    TextOperation::Insert { position, text: text.to_string() }
+}
+
+fn delete(position: usize, length: usize) -> TextOperation {
+    TextOperation::Delete { position, length }
+}
+
+fn without_empty(ops: Vec<TextOperation>) -> Vec<TextOperation> {
+    ops.into_iter()
+        .filter(|op| !matches!(op, TextOperation::Delete { length: 0, .. }))
+        .collect()
+}
+
+fn len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // applies two concurrent edits to the same text in both orders, checking that both end up with the same text.
+    fn converge(text: &str, a: &[TextOperation], b: &[TextOperation]) -> String {
+        let (a_after_b, b_after_a) = transform_both(a, b).unwrap();
+        let left = apply(&apply(text, b).unwrap(), &a_after_b).unwrap();
+        let right = apply(&apply(text, a).unwrap(), &b_after_a).unwrap();
+        assert_eq!(left, right, "edits {:?} and {:?} diverge on {:?}", a, b, text);
+        left
+    }
+
+    #[test]
+    fn inserts_at_the_same_position_keep_the_earlier_first() {
+        assert_eq!(converge("abcd", &[insert(2, "X")], &[insert(2, "Y")]), "abYXcd");
+        assert_eq!(transform(&[insert(2, "X")], &[insert(2, "Y")]), Some(vec![insert(3, "X")]));
+    }
+
+    #[test]
+    fn inserts_at_different_positions() {
+        assert_eq!(converge("abcd", &[insert(1, "X")], &[insert(3, "Y")]), "aXbcYd");
+        assert_eq!(converge("abcd", &[insert(4, "X")], &[insert(0, "Y")]), "YabcdX");
+    }
+
+    #[test]
+    fn deletes_at_the_same_position() {
+        assert_eq!(converge("abcdef", &[delete(1, 2)], &[delete(1, 2)]), "adef");
+        assert_eq!(transform(&[delete(1, 2)], &[delete(1, 2)]), Some(vec![]));
+        assert_eq!(converge("abcdef", &[delete(1, 1)], &[delete(1, 3)]), "aef");
+    }
+
+    #[test]
+    fn overlapping_deletes() {
+        assert_eq!(converge("abcdefg", &[delete(1, 3)], &[delete(2, 3)]), "afg");
+        assert_eq!(converge("abcdefg", &[delete(2, 3)], &[delete(1, 3)]), "afg");
+        assert_eq!(converge("abcdefg", &[delete(0, 7)], &[delete(2, 2)]), "");
+    }
+
+    #[test]
+    fn insert_and_delete_at_the_same_position() {
+        assert_eq!(converge("abcdef", &[insert(2, "X")], &[delete(2, 2)]), "abXef");
+        assert_eq!(converge("abcdef", &[delete(2, 2)], &[insert(2, "X")]), "abXef");
+    }
+
+    #[test]
+    fn insert_inside_a_deleted_range_is_kept() {
+        assert_eq!(converge("abcdefg", &[insert(3, "X")], &[delete(1, 4)]), "aXfg");
+        assert_eq!(converge("abcdefg", &[delete(1, 4)], &[insert(3, "X")]), "aXfg");
+    }
+
+    #[test]
+    fn edits_of_several_operations() {
+        let a = [insert(0, "Hi "), delete(5, 2)];
+        let b = [delete(0, 1), insert(3, "Z"), delete(4, 1)];
+        converge("abcdefgh", &a, &b);
+        converge("abcdefgh", &b, &a);
+    }
+
+    #[test]
+    fn positions_count_surrogate_pairs_as_two_units() {
+        // the emoji takes two utf-16 code units
+        assert_eq!(apply("a😀b", &[insert(3, "!")]).unwrap(), "a😀!b");
+        assert_eq!(apply("a😀b", &[delete(1, 2)]).unwrap(), "ab");
+        assert_eq!(converge("abc", &[insert(3, "!")], &[insert(1, "😀")]), "a😀bc!");
+        assert_eq!(converge("a😀b", &[delete(1, 2)], &[insert(3, "x")]), "axb");
+    }
+
+    #[test]
+    fn edits_splitting_a_surrogate_pair_fail() {
+        assert!(apply("a😀b", &[insert(2, "x")]).is_err());
+        assert!(apply("a😀b", &[delete(1, 1)]).is_err());
+    }
+
+    #[test]
+    fn edits_outside_of_the_text_fail() {
+        assert!(apply("abc", &[insert(4, "x")]).is_err());
+        assert!(apply("abc", &[delete(2, 2)]).is_err());
+        assert_eq!(apply("abc", &[insert(3, "d"), delete(0, 1)]).unwrap(), "bcd");
+    }
+
+    #[test]
+    fn operations_past_the_end_of_the_text_are_refused() {
+        assert!(check(4, &[delete(1, usize::MAX)]).is_err());
+        assert!(check(4, &[delete(usize::MAX, 1)]).is_err());
+        assert!(check(4, &[insert(5, "X")]).is_err());
+        assert!(check(4, &[insert(4, "X"), delete(0, 5)]).is_ok());
+        assert!(check(4, &[delete(0, 4), insert(1, "X")]).is_err());
+        assert!(apply("abcd", &[delete(1, usize::MAX)]).is_err());
+    }
+
+    #[test]
+    fn overflowing_positions_are_not_transformed() {
+        assert_eq!(transform(&[delete(1, usize::MAX)], &[insert(0, "X")]), None);
+        assert_eq!(transform(&[insert(0, "X")], &[delete(usize::MAX, 1)]), None);
+        assert_eq!(transform(&[delete(2, 1)], &[delete(1, usize::MAX)]), None);
+    }
+
+    #[test]
+    fn length_before_undoes_applied_operations() {
+        assert_eq!(length_before("aXbcd", &[insert(1, "X")]), Some(4));
+        assert_eq!(length_before("ad", &[delete(1, 2)]), Some(4));
+        assert_eq!(length_before("", &[insert(0, "X")]), None);
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use rocket::tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::types::user_service::User;
+
+
+// how long a ticket can be used to connect after it was issued.
+pub const TICKET_TTL: Duration = Duration::from_secs(30);
+
+struct Ticket {
+    user: User,
+    case_id: String,
+    expires_at: Instant
+}
+
+// issues the tickets websocket clients connect with, so session tokens never end up in urls.
+// a ticket belongs to a user and a case, and can be used once within a short time of being issued.
+#[derive(Default)]
+pub struct TicketService {
+    tickets: Mutex<HashMap<String, Ticket>>
+}
+
+impl TicketService {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn issue(&self, user: &User, case_id: &String) -> String {
+        let mut tickets = self.tickets.lock().await;
+
+        // tickets that were never used are dropped here, rather than by a task of their own
+        let now = Instant::now();
+        tickets.retain(|_, ticket| ticket.expires_at > now);
+
+        let id = Uuid::new_v4().to_string();
+        tickets.insert(id.to_string(), Ticket { user: user.clone(), case_id: case_id.to_string(), expires_at: now + TICKET_TTL });
+        id
+    }
+
+    // uses up the ticket, returning the user it was issued to if it is still valid for the case.
+    pub async fn redeem(&self, ticket: &String, case_id: &String) -> Option<User> {
+        let ticket = self.tickets.lock().await.remove(ticket)?;
+        match ticket.expires_at > Instant::now() && &ticket.case_id == case_id {
+            true => Some(ticket.user),
+            false => None
+        }
+    }
+
+}
@@ -6,10 +6,12 @@ use std::time::{Instant, Duration};
 use async_std::{task, sync::RwLock};
 
 use super::token::TokenService;
-use crate::types::user_service::{CheckTokenBody, CheckTokenErrorResponse};
+use super::verifier::{self, LocalVerifier, VerifyError};
+use crate::types::user_service::{CheckTokenBody, CheckTokenErrorResponse, User};
 
 
 pub struct CacheEntry {
+    pub user: User,
     pub expires_at: Instant
 }
 
@@ -18,14 +20,27 @@ pub struct UserService {
     token: TokenService,
     domain: String,
     cache: Arc<RwLock<HashMap<String, CacheEntry>>>,
-    ttl: Duration
+    // how long an accepted token is taken to be valid without checking it again, along with the groups it lists
+    ttl: Duration,
+    // verifies tokens without asking the user service, when configured
+    local: Option<LocalVerifier>,
+    // whether tokens verified locally are still checked with the user service, e.g. to catch revoked sessions
+    revocation_check: bool,
+    groups_claim: String,
+    // the group whose members administer the service, e.g. upload templates. nobody does when not configured
+    admin_group: Option<String>
 }
 
 impl UserService {
 
     pub fn new() -> Arc<Self> {
+        let client = Client::new();
         let s = Arc::new(Self {
-            client: Client::new(),
+            local: LocalVerifier::from_env(client.clone()),
+            revocation_check: env::var("USER_TOKEN_REVOCATION_CHECK").is_ok_and(|v| v == "true"),
+            groups_claim: verifier::groups_claim(),
+            admin_group: env::var("ADMIN_GROUP").ok().filter(|v| !v.is_empty()),
+            client,
             token: TokenService::new(),
             domain: env::var("USER_SERVICE_DOMAIN").expect("USER_SERVICE_DOMAIN environment variable"),
             cache: Arc::new(RwLock::new(HashMap::new())),
@@ -35,15 +50,43 @@ impl UserService {
         s
     }
 
-    // request user serivce for token verification
-    pub async fn check(&self, token: String) -> Result<(), Box<dyn std::error::Error>> {
+    // whether the user is a member of the admin group.
+    pub fn is_admin(&self, user: &User) -> bool {
+        self.admin_group.as_ref().is_some_and(|v| user.is_member(v))
+    }
+
+    // verify the token, returning the user the token belongs to. tokens are verified locally when configured, and by
+    // the user service otherwise, or when the keys to verify them locally can't be fetched
+    pub async fn check(&self, token: String) -> Result<User, Box<dyn std::error::Error>> {
         
          // check cache if the token has been accepted recently
-        if self.get(&token.to_string()).await {
-            return Ok(())
+        if let Some(user) = self.get(&token.to_string()).await {
+            return Ok(user)
         }
 
-        // otherwise do a normal check
+        let (user, valid_for) = match &self.local {
+            Some(local) => match local.verify(&token).await {
+                Ok((user, valid_for)) => match self.revocation_check {
+                    true => (self.check_remote(&token).await?, valid_for),
+                    false => (user, valid_for)
+                },
+                Err(VerifyError::Rejected(e)) => return Err(e.into()),
+                Err(VerifyError::Unavailable(e)) => {
+                    eprintln!("error verifying token locally, checking with user service: {}", e);
+                    (self.check_remote(&token).await?, self.ttl)
+                }
+            },
+            None => (self.check_remote(&token).await?, self.ttl)
+        };
+
+        // add accepted token to cache, no longer than it is valid
+        self.set(&token.to_string(), &user, valid_for.min(self.ttl)).await;
+        Ok(user)
+    }
+
+    // request user serivce for token verification. the user service only tells whether it accepts the token,
+    // who the token belongs to is read from the token itself
+    async fn check_remote(&self, token: &String) -> Result<User, Box<dyn std::error::Error>> {
         let url = format!("{}/api/internal/check_user", self.domain);
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
@@ -56,11 +99,7 @@ impl UserService {
             .await?;
 
         match response.status().is_success() {
-            true => {
-                // add accepted token to cache
-                self.set(&token.to_string()).await;
-                Ok(())
-            },
+            true => Ok(verifier::read_user(&verifier::read_claims(token)?, &self.groups_claim)?),
             false => {
                 let err_body = response.json::<CheckTokenErrorResponse>().await?;
                 Err(err_body.error.into())
@@ -68,24 +107,24 @@ impl UserService {
         }
     }
 
-    async fn get(&self, token: &String) -> bool {
+    async fn get(&self, token: &String) -> Option<User> {
         let mut cache = self.cache.write().await;
         match cache.get(token) {
             Some(entry) => {
                 if entry.expires_at > Instant::now() {
-                    return true
+                    Some(entry.user.clone())
                 } else {
                     cache.remove(token);
-                    return false
+                    None
                 }
             },
-            None => false
+            None => None
         }
     }
 
-    async fn set(&self, token: &String) {
+    async fn set(&self, token: &String, user: &User, ttl: Duration) {
         let mut cache = self.cache.write().await;
-        cache.insert(token.to_string(), CacheEntry { expires_at: Instant::now() + self.ttl });
+        cache.insert(token.to_string(), CacheEntry { user: user.clone(), expires_at: Instant::now() + ttl });
     }
 
     async fn cleanup_tokens(&self) {
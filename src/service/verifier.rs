@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use async_std::sync::RwLock;
+use chrono::Utc;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::types::user_service::User;
+
+
+// how long fetched keys are used before they are fetched again
+const JWKS_TTL: Duration = Duration::from_secs(3600);
+// how often keys are fetched at most, e.g. when tokens are signed with a key that was rotated in since the last fetch
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(30);
+
+pub enum VerifyError {
+    // the token is not valid, whatever the user service would say about it
+    Rejected(String),
+    // the keys to verify the token with can't be fetched, so the token can't be verified here
+    Unavailable(String)
+}
+
+enum Keys {
+    Secret(DecodingKey),
+    Jwks { url: String, cache: RwLock<KeyCache> }
+}
+
+#[derive(Default)]
+struct KeyCache {
+    keys: Option<JwkSet>,
+    fetched_at: Option<Instant>,
+    attempted_at: Option<Instant>
+}
+
+// verifies user tokens without asking the user service, either with a secret shared with it (HS256) or with the
+// public keys it publishes as a jwks document (RS256 or ES256).
+pub struct LocalVerifier {
+    client: Client,
+    keys: Keys,
+    issuer: Option<String>,
+    audience: Option<String>,
+    groups_claim: String
+}
+
+impl LocalVerifier {
+
+    // the verifier configured by the environment, if tokens are to be verified locally at all.
+    pub fn from_env(client: Client) -> Option<Self> {
+        match env::var("USER_TOKEN_VERIFICATION").as_deref() {
+            Ok("local") => {},
+            Ok("remote") | Err(_) => return None,
+            Ok(v) => panic!("USER_TOKEN_VERIFICATION must be local or remote, not {}", v)
+        }
+        let keys = match (env::var("USER_TOKEN_SECRET"), env::var("USER_TOKEN_JWKS_URL")) {
+            (Ok(_), Ok(_)) => panic!("USER_TOKEN_SECRET and USER_TOKEN_JWKS_URL are both set, local token verification takes one of them"),
+            (Ok(secret), _) => Keys::Secret(DecodingKey::from_secret(secret.as_bytes())),
+            (_, Ok(url)) => Keys::Jwks { url, cache: RwLock::new(KeyCache::default()) },
+            _ => panic!("USER_TOKEN_SECRET or USER_TOKEN_JWKS_URL environment variable for local token verification")
+        };
+        Some(Self {
+            client,
+            keys,
+            issuer: env::var("USER_TOKEN_ISSUER").ok(),
+            audience: env::var("USER_TOKEN_AUDIENCE").ok(),
+            groups_claim: groups_claim()
+        })
+    }
+
+    // verifies the signature and claims of the token, returning the user it belongs to and how long it is valid for.
+    pub async fn verify(&self, token: &str) -> Result<(User, Duration), VerifyError> {
+        let header = decode_header(token).map_err(|e| VerifyError::Rejected(e.to_string()))?;
+        let key = match &self.keys {
+            Keys::Secret(key) => {
+                if header.alg != Algorithm::HS256 {
+                    return Err(VerifyError::Rejected(format!("unexpected algorithm {:?}", header.alg)));
+                }
+                key.clone()
+            },
+            Keys::Jwks { url, cache } => {
+                if !matches!(header.alg, Algorithm::RS256 | Algorithm::ES256) {
+                    return Err(VerifyError::Rejected(format!("unexpected algorithm {:?}", header.alg)));
+                }
+                let kid = header.kid.as_ref().ok_or(VerifyError::Rejected("token without key id".into()))?;
+                let jwk = self.find_key(url, cache, kid).await?;
+                if jwk.common.key_algorithm.is_some_and(|v| Algorithm::from_str(&v.to_string()).ok() != Some(header.alg)) {
+                    return Err(VerifyError::Rejected(format!("key {} is not for {:?}", kid, header.alg)));
+                }
+                DecodingKey::from_jwk(&jwk).map_err(|e| VerifyError::Rejected(format!("invalid key {}: {}", kid, e)))?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false
+        }
+        let claims = decode::<HashMap<String, Value>>(token, &key, &validation)
+            .map_err(|e| VerifyError::Rejected(e.to_string()))?
+            .claims;
+
+        let user = read_user(&claims, &self.groups_claim).map_err(VerifyError::Rejected)?;
+        let exp = claims.get("exp").and_then(Value::as_i64).unwrap_or_default();
+        let valid_for = Duration::from_secs((exp - Utc::now().timestamp()).max(0) as u64);
+        Ok((user, valid_for))
+    }
+
+    async fn find_key(&self, url: &str, cache: &RwLock<KeyCache>, kid: &str) -> Result<Jwk, VerifyError> {
+        {
+            let cache = cache.read().await;
+            let fresh = cache.fetched_at.is_some_and(|v| v.elapsed() < JWKS_TTL);
+            if let Some(jwk) = cache.keys.as_ref().and_then(|v| v.find(kid)).filter(|_| fresh) {
+                return Ok(jwk.clone());
+            }
+        }
+
+        // the keys are stale, or the token is signed with a key that was added since they were fetched
+        let mut cache = cache.write().await;
+        if cache.attempted_at.is_none_or(|v| v.elapsed() >= JWKS_MIN_REFRESH) {
+            cache.attempted_at = Some(Instant::now());
+            match self.fetch_keys(url).await {
+                Ok(keys) => {
+                    cache.keys = Some(keys);
+                    cache.fetched_at = Some(Instant::now());
+                },
+                Err(e) => eprintln!("error fetching keys from {}: {}", url, e)
+            }
+        }
+        // stale keys may have been revoked since, so tokens are left to the user service until new ones are fetched
+        let fresh = cache.fetched_at.is_some_and(|v| v.elapsed() < JWKS_TTL);
+        match (cache.keys.as_ref().and_then(|v| v.find(kid)), fresh) {
+            (_, false) => Err(VerifyError::Unavailable(format!("no current keys fetched from {}", url))),
+            (Some(jwk), true) => Ok(jwk.clone()),
+            (None, true) => Err(VerifyError::Rejected(format!("unknown key {}", kid)))
+        }
+    }
+
+    async fn fetch_keys(&self, url: &str) -> Result<JwkSet, reqwest::Error> {
+        self.client.get(url).send().await?.error_for_status()?.json::<JwkSet>().await
+    }
+
+}
+
+// the claim the groups of a user are read from.
+pub fn groups_claim() -> String {
+    env::var("USER_TOKEN_GROUPS_CLAIM").unwrap_or_else(|_| "groups".into())
+}
+
+// reads the claims of a token without verifying it, for tokens the user service has accepted already.
+pub fn read_claims(token: &str) -> Result<HashMap<String, Value>, String> {
+    let header = decode_header(token).map_err(|e| format!("not a jwt: {}", e))?;
+    let mut validation = Validation::new(header.alg);
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.set_required_spec_claims(&["sub"]);
+    match decode::<HashMap<String, Value>>(token, &DecodingKey::from_secret(&[]), &validation) {
+        Ok(v) => Ok(v.claims),
+        Err(e) => Err(format!("invalid claims: {}", e))
+    }
+}
+
+// the user a token belongs to, which is its subject, along with the groups listed by the groups claim.
+// a token without the groups claim is rejected rather than taken to belong to a user without groups, as that
+// would mostly mean the claim is configured wrong.
+pub fn read_user(claims: &HashMap<String, Value>, groups_claim: &str) -> Result<User, String> {
+    let user_id = match claims.get("sub").and_then(Value::as_str) {
+        Some(v) => v.to_string(),
+        None => return Err("invalid subject".into())
+    };
+    let groups = match claims.get(groups_claim) {
+        Some(Value::Array(v)) => v.iter().filter_map(Value::as_str).map(String::from).collect(),
+        Some(_) => return Err(format!("the {} claim is not a list of groups", groups_claim)),
+        None => return Err(format!("missing {} claim", groups_claim))
+    };
+    Ok(User { user_id, groups })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn verifier() -> LocalVerifier {
+        LocalVerifier {
+            client: Client::new(),
+            keys: Keys::Secret(DecodingKey::from_secret(SECRET)),
+            issuer: None,
+            audience: None,
+            groups_claim: "groups".into()
+        }
+    }
+
+    fn token(alg: Algorithm, claims: Value) -> String {
+        encode(&Header::new(alg), &claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    fn in_an_hour() -> i64 {
+        Utc::now().timestamp() + 3600
+    }
+
+    fn rejected(result: Result<(User, Duration), VerifyError>) -> bool {
+        matches!(result, Err(VerifyError::Rejected(_)))
+    }
+
+    #[rocket::async_test]
+    async fn accepts_a_valid_token() {
+        let token = token(Algorithm::HS256, json!({ "sub": "user", "groups": ["a", "b"], "exp": in_an_hour() }));
+        let (user, valid_for) = verifier().verify(&token).await.ok().unwrap();
+        assert_eq!(user.user_id, "user");
+        assert_eq!(user.groups, vec!["a", "b"]);
+        assert!(valid_for > Duration::from_secs(3500) && valid_for <= Duration::from_secs(3600));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_other_algorithms() {
+        let token = token(Algorithm::HS384, json!({ "sub": "user", "groups": [], "exp": in_an_hour() }));
+        assert!(rejected(verifier().verify(&token).await));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_other_secrets() {
+        let claims = json!({ "sub": "user", "groups": [], "exp": in_an_hour() });
+        let token = encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(b"other")).unwrap();
+        assert!(rejected(verifier().verify(&token).await));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_expired_tokens() {
+        let token = token(Algorithm::HS256, json!({ "sub": "user", "groups": [], "exp": Utc::now().timestamp() - 3600 }));
+        assert!(rejected(verifier().verify(&token).await));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_tokens_without_a_subject() {
+        let token = token(Algorithm::HS256, json!({ "groups": [], "exp": in_an_hour() }));
+        assert!(rejected(verifier().verify(&token).await));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_tokens_without_the_groups_claim() {
+        let token = token(Algorithm::HS256, json!({ "sub": "user", "exp": in_an_hour() }));
+        assert!(rejected(verifier().verify(&token).await));
+    }
+
+    #[rocket::async_test]
+    async fn reads_the_groups_from_the_configured_claim() {
+        let token = token(Algorithm::HS256, json!({ "sub": "user", "roles": ["a"], "groups": ["b"], "exp": in_an_hour() }));
+        let verifier = LocalVerifier { groups_claim: "roles".into(), ..verifier() };
+        let (user, _) = verifier.verify(&token).await.ok().unwrap();
+        assert_eq!(user.groups, vec!["a"]);
+    }
+
+    #[test]
+    fn reads_the_user_of_tokens_accepted_by_the_user_service() {
+        let token = token(Algorithm::HS256, json!({ "sub": "user", "groups": ["a"], "exp": Utc::now().timestamp() - 3600 }));
+        let user = read_user(&read_claims(&token).unwrap(), "groups").unwrap();
+        assert_eq!(user.user_id, "user");
+        assert_eq!(user.groups, vec!["a"]);
+    }
+}
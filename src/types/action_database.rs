@@ -0,0 +1,56 @@
+use rocket::FromFormField;
+use serde::{Deserialize, Serialize};
+
+use crate::types::case_database::Documentation;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionStatus {
+    Open,
+    #[field(value = "in_progress")]
+    InProgress,
+    Done,
+    Cancelled
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionPriority {
+    Low,
+    Medium,
+    High,
+    Critical
+}
+
+// a remediation step planned for a control of a case, or a subcontrol in it. the due date is unix milliseconds.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ActionItem {
+    pub action_id: String,
+    pub case_id: String,
+    pub group_id: String,
+    pub control_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subcontrol_id: Option<String>,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<i64>,
+    pub status: ActionStatus,
+    pub priority: ActionPriority,
+    #[serde(default)]
+    pub evidence: Vec<Documentation>,
+    pub created_by: String,
+    pub created_at: i64,
+    pub updated_at: i64
+}
+
+// which action items to read, either those of a case or those of every case in a group.
+#[derive(Debug, Default)]
+pub struct ActionFilter {
+    pub case_id: Option<String>,
+    pub group_id: Option<String>,
+    pub status: Option<ActionStatus>,
+    pub owner: Option<String>
+}
@@ -0,0 +1,84 @@
+use rocket::FromForm;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::types::action_database::{ActionPriority, ActionStatus};
+use crate::types::case_database::Documentation;
+
+
+// the subcontrol is left out for frameworks without subcontrols. a new action item is open with medium priority
+// unless told otherwise.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateActionBody {
+    pub control_id: String,
+    #[serde(default)]
+    pub subcontrol_id: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub due_date: Option<i64>,
+    #[serde(default)]
+    pub status: Option<ActionStatus>,
+    #[serde(default)]
+    pub priority: Option<ActionPriority>,
+    #[serde(default)]
+    pub evidence: Vec<Documentation>
+}
+
+// only the fields given are changed. the owner and due date are cleared when given as null.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UpdateActionBody {
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub owner: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub due_date: Option<Option<i64>>,
+    pub status: Option<ActionStatus>,
+    pub priority: Option<ActionPriority>,
+    pub evidence: Option<Vec<Documentation>>
+}
+
+// tells a field given as null, Some(None), from a field left out, None.
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, FromForm)]
+pub struct ActionQuery {
+    pub status: Option<ActionStatus>,
+    pub owner: Option<String>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(json: &str) -> UpdateActionBody {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn fields_left_out_are_kept() {
+        let body = body(r#"{"title": "patch"}"#);
+        assert_eq!(body.owner, None);
+        assert_eq!(body.due_date, None);
+    }
+
+    #[test]
+    fn fields_given_as_null_are_cleared() {
+        let body = body(r#"{"owner": null, "due_date": null}"#);
+        assert_eq!(body.owner, Some(None));
+        assert_eq!(body.due_date, Some(None));
+    }
+
+    #[test]
+    fn fields_given_are_set() {
+        let body = body(r#"{"owner": "user", "due_date": 1700000000000}"#);
+        assert_eq!(body.owner, Some(Some("user".into())));
+        assert_eq!(body.due_date, Some(Some(1700000000000)));
+    }
+}
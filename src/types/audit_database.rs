@@ -0,0 +1,53 @@
+use mongodb::bson::Bson;
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Change,
+    Revert,
+    Rename,
+    Delete,
+    ImplementationGroup,
+    Migrate
+}
+
+// a single change made to a case, who made it and what the value was before and after.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AuditRecord {
+    pub case_id: String,
+    pub user_id: String,
+    pub action: AuditAction,
+    pub path: Option<String>,
+    pub control_id: Option<String>,
+    pub subcontrol_id: Option<String>,
+    pub field: Option<String>,
+    pub old_value: Option<Bson>,
+    pub new_value: Option<Bson>,
+    pub revision: Option<i64>,
+    pub timestamp: i64
+}
+
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub user_id: Option<String>,
+    pub control_id: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AuditHistory {
+    pub records: Vec<AuditRecord>,
+    pub page: u64,
+    pub page_size: i64,
+    pub total: u64
+}
+
+// a point in a case's history, either a revision or a unix timestamp in milliseconds.
+#[derive(Debug)]
+pub enum HistoryPoint {
+    Revision(i64),
+    Timestamp(i64)
+}
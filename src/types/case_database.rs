@@ -1,58 +1,41 @@
 
-//use mongodb::bson;
+use mongodb::bson::{Bson, Document};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(untagged)]
-pub enum Case {
-    CIS18(CIS18Case),
-    NIS2(NIS2Case)
-}
+use crate::types::collaboration_handler::Event;
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct CIS18Case {
-    pub case_id: String,
-    pub group_id: String,
-    pub name: String,
-    pub framework: String,
-    pub implementation_group: i32,
-    pub controls: Vec<CIS18Control>
+// what every case has, whichever framework it follows. the rest of a case is laid out by its framework,
+// which is what reads it.
+pub trait CaseDocument {
+    fn framework(&self) -> &str;
+    fn revision(&self) -> i64;
+    // the version and locale of the template the case was created from or last migrated to.
+    fn template(&self) -> (Option<i32>, Option<&str>);
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct CIS18Control {
-    pub id: String,
-    pub title: String,
-    pub description: String,
-    pub subcontrols: Vec<CIS18SubControl>
-}
+impl CaseDocument for Document {
+    fn framework(&self) -> &str {
+        self.get_str("framework").unwrap_or_default()
+    }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct CIS18SubControl {
-    pub id: String,
-    pub title: String,
-    pub description: String,
-    pub observation: String,
-    pub as_is_score: i32,
-    pub plan: String,
-    pub to_be_score: i32,
-    pub soa: String,
-    pub implementation_group: Vec<i32>,
-    pub documentation: Vec<Documentation>
-}
+    // cases created before revisions were introduced don't have one
+    fn revision(&self) -> i64 {
+        match self.get("revision") {
+            Some(Bson::Int64(v)) => *v,
+            Some(Bson::Int32(v)) => *v as i64,
+            _ => 0
+        }
+    }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Documentation {
-    name: String,
-    src: String,
+    fn template(&self) -> (Option<i32>, Option<&str>) {
+        (self.get_i32("template_version").ok(), self.get_str("template_locale").ok())
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct NIS2Case {
-    pub case_id: String,
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Documentation {
     pub name: String,
-    pub group_id: String,
-    pub framework: String
+    pub src: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -68,4 +51,33 @@ pub struct CaseMetadata {
     pub name: String,
     pub framework: String,
     pub implementation_group: Option<i32>
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CaseRevision {
+    #[serde(default)]
+    pub revision: i64
+}
+
+// an applied change, stored in the case's change log under the revision it produced.
+// this is also what is broadcasted to the other clients on the case.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ChangeRecord {
+    pub case_id: String,
+    pub revision: i64,
+    pub user_id: String,
+    pub path: String,
+    #[serde(flatten)]
+    pub event: Event,
+    pub timestamp: i64
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ChangeOutcome {
+    Applied { record: ChangeRecord, old_value: Bson, new_value: Bson },
+    // the change itself is invalid
+    Rejected { revision: i64, reason: String },
+    // the change was made against a version of the case that conflicts with the latest one
+    Stale { revision: i64, reason: String }
+}
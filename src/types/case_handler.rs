@@ -1,11 +1,24 @@
+use rocket::FromForm;
 use serde::{Deserialize, Serialize};
 
+use crate::types::crosswalk::ProjectedItem;
+use crate::types::framework::MigrationReport;
+
+// the implementation group is only used by frameworks that have them, and so is keeping what falls outside of it.
+// without a template version the latest version of the framework's template is used. the case is owned by the user
+// the token belongs to.
 #[derive(Debug, Deserialize, Serialize)]
-pub struct CreateCIS18CaseBody {
-    pub user_id: String,
+pub struct CreateCaseBody {
     pub group_id: String,
     pub name: String,
-    pub implementation_group: i32
+    #[serde(default)]
+    pub implementation_group: Option<i32>,
+    #[serde(default)]
+    pub keep_out_of_scope: bool,
+    #[serde(default)]
+    pub template_version: Option<i32>,
+    #[serde(default)]
+    pub locale: Option<String>
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -18,3 +31,95 @@ pub struct RenameCaseBody {
     pub name: String
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImplementationGroupBody {
+    pub implementation_group: i32
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImplementationGroupResponse {
+    pub revision: i64,
+    pub implementation_group: i32
+}
+
+// moves a case onto another version of its template, the latest if none is given, in the locale of the case
+// unless another is given. nothing is changed unless dry_run is turned off.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MigrateCaseBody {
+    pub template_version: Option<i32>,
+    pub locale: Option<String>,
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MigrateCaseResponse {
+    pub dry_run: bool,
+    // the revision of the case after the migration, or the revision it would apply to on a dry run
+    pub revision: i64,
+    pub from_version: Option<i32>,
+    pub to_version: Option<i32>,
+    #[serde(flatten)]
+    pub report: MigrationReport
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProjectionResponse {
+    pub source: String,
+    pub target: String,
+    pub items: Vec<ProjectedItem>
+}
+
+// creates a case of another framework in the same group, filled in from what the case says about it.
+// without a template version the latest version of the other framework's template is used.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SeedCaseBody {
+    pub name: String,
+    #[serde(default)]
+    pub template_version: Option<i32>,
+    #[serde(default)]
+    pub locale: Option<String>
+}
+
+// paging and filters for a case's history, timestamps are unix milliseconds.
+#[derive(Debug, FromForm)]
+pub struct HistoryQuery {
+    pub page: Option<u64>,
+    pub page_size: Option<i64>,
+    pub user_id: Option<String>,
+    pub control_id: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>
+}
+
+// restores a case, or a single field of it, to how it was at a revision or a unix timestamp in milliseconds.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RevertCaseBody {
+    pub revision: Option<i64>,
+    pub timestamp: Option<i64>,
+    pub field: Option<FieldTarget>
+}
+
+// a field of a case, found through ids the same way as the field of a change.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FieldTarget {
+    pub ids: Vec<String>,
+    pub field: String
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RevertCaseResponse {
+    pub revision: i64,
+    pub reverted: Vec<String>,
+    pub rejected: Vec<RevertRejection>
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RevertRejection {
+    pub path: String,
+    pub reason: String
+}
@@ -1,38 +1,253 @@
-use serde::Deserialize;
+use mongodb::bson::Document;
+use serde::{Deserialize, Serialize};
 
+use crate::types::action_database::ActionItem;
+use crate::types::case_database::{ChangeRecord, Documentation};
+use crate::types::comment_database::Comment;
 
-#[derive(Debug, Deserialize)]
+
+// the longest text a free text field can hold, counted in characters.
+pub const MAX_TEXT_LENGTH: usize = 20000;
+
+
+// a ticket to connect to a case's collaboration socket with, valid once for the given number of seconds.
+#[derive(Debug, Serialize)]
+pub struct TicketResponse {
+    pub ticket: String,
+    pub expires_in: u64
+}
+
+// a message received from a client, carrying the revision of the case the client made it against.
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Message {
+    #[serde(flatten)]
     pub event: Event,
-    pub data: Change
+    #[serde(default)]
+    pub revision: Option<i64>
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
 pub enum Event {
-    TextChange,
-    DropdownChange,
-    DocumentationChange
+    TextChange(Change),
+    DropdownChange(Change),
+    DocumentationChange(Change),
+    Focus(Option<Focus>),
+    // asks for what changed since the message's revision, or the whole case if there is no revision
+    Resync,
+    // the server replaced the structure of the case, e.g. by migrating it. only found in the change log,
+    // clients resync rather than apply it
+    Restructure(String)
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-pub enum Change {
-    CIS18Change(CIS18Change),
-    NIS2Change
+impl Event {
+    // the change carried by the event, if it is one that edits the case.
+    pub fn change(&self) -> Option<&Change> {
+        match self {
+            Event::TextChange(change) | Event::DropdownChange(change) | Event::DocumentationChange(change) => Some(change),
+            Event::Focus(_) | Event::Resync | Event::Restructure(_) => None
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct CIS18Change {
-    pub control_id: String,
-    pub subcontrol_id: String,
+// a change to a field of a case. the ids lead to the item the field belongs to, outermost first, as the case's
+// framework has them, e.g. a control and one of its safeguards for cis18 or just a measure for nis2.
+// a change either replaces the field with a value or edits its text through operations.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(from = "ChangeData")]
+pub struct Change {
+    pub ids: Vec<String>,
     pub field: String,
-    pub value: TextOrIntValue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<TextOrIntValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operations: Option<Vec<TextOperation>>
+}
+
+impl Change {
+    // the same text edit with its operations replaced, used once they have been transformed.
+    pub fn with_operations(&self, operations: Vec<TextOperation>) -> Change {
+        Change { operations: Some(operations), ..self.clone() }
+    }
+}
+
+// a change as it is received or read from the change log. changes logged before changes were the same for every
+// framework name their item through the id fields of their framework rather than a list of ids.
+#[derive(Deserialize)]
+struct ChangeData {
+    #[serde(default)]
+    ids: Vec<String>,
+    #[serde(default, alias = "measure_id", alias = "subcategory_id")]
+    control_id: Option<String>,
+    #[serde(default)]
+    subcontrol_id: Option<String>,
+    field: String,
+    #[serde(default)]
+    value: Option<TextOrIntValue>,
+    #[serde(default)]
+    operations: Option<Vec<TextOperation>>
+}
+
+impl From<ChangeData> for Change {
+    fn from(data: ChangeData) -> Self {
+        let ids = match data.ids.is_empty() {
+            true => data.control_id.into_iter().chain(data.subcontrol_id).collect(),
+            false => data.ids
+        };
+        Change { ids, field: data.field, value: data.value, operations: data.operations }
+    }
+}
+
+// positions and lengths are counted in utf-16 code units.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextOperation {
+    Insert { position: usize, text: String },
+    Delete { position: usize, length: usize }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum TextOrIntValue {
     String(String),
-    Number(i32)
-}
\ No newline at end of file
+    Number(i32),
+    Documentation(Vec<Documentation>)
+}
+
+// the field a user is currently editing, found through ids the same way as the field of a change.
+// a focus event without data means the user stopped editing.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Focus {
+    pub ids: Vec<String>,
+    pub field: String
+}
+
+// replies sent only to the client that sent a message, telling it whether a change was applied.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum Reply {
+    Ack {
+        revision: i64
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        revision: Option<i64>
+    },
+    // the client's copy of the case has fallen behind in a way the change can't be applied to, it has to resync first
+    ResyncRequired {
+        revision: i64,
+        reason: String
+    },
+    // the whole case, sent when a client connects and when it resyncs from too far behind
+    Snapshot {
+        revision: i64,
+        case: Document
+    },
+    // the changes a resyncing client missed, oldest first
+    Changes {
+        revision: i64,
+        changes: Vec<ChangeRecord>
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidMessage,
+    InvalidChange,
+    Unsupported,
+    // the client's role on the case doesn't allow changing it
+    ReadOnly,
+    InternalError
+}
+
+// presence events sent from the server, so clients can show who is on the case and what they are editing.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum Presence {
+    UserJoined(Collaborator),
+    UserLeft(Collaborator),
+    PresenceSnapshot(Vec<Collaborator>),
+    Focus(Collaborator)
+}
+
+// action item events sent from the server, so the remediation plan of a case updates live for its editors.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum ActionEvent {
+    ActionItemCreated(ActionItem),
+    ActionItemUpdated(ActionItem),
+    ActionItemDeleted {
+        action_id: String
+    }
+}
+
+// comment events sent from the server, so the comments on a case update live for everyone on it.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum CommentEvent {
+    CommentAdded(Comment),
+    CommentDeleted {
+        comment_id: String
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Collaborator {
+    pub connection_id: String,
+    pub user_id: String,
+    pub focus: Option<Focus>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(event: &str) -> Change {
+        match serde_json::from_str::<Message>(event).unwrap().event {
+            Event::TextChange(change) | Event::DropdownChange(change) | Event::DocumentationChange(change) => change,
+            event => panic!("not a change: {:?}", event)
+        }
+    }
+
+    #[test]
+    fn change_with_ids() {
+        let change = parse(r#"{"event": "dropdown_change", "data": {"ids": ["1", "1.1"], "field": "as_is_score", "value": 3}, "revision": 4}"#);
+        assert_eq!(change.ids, ["1", "1.1"]);
+        assert_eq!(change.field, "as_is_score");
+        assert!(matches!(change.value, Some(TextOrIntValue::Number(3))));
+        assert!(change.operations.is_none());
+    }
+
+    #[test]
+    fn text_edit_with_ids() {
+        let change = parse(r#"{"event": "text_change", "data": {"ids": ["21.2.a"], "field": "notes", "operations": [{"type": "insert", "position": 0, "text": "a"}]}}"#);
+        assert_eq!(change.ids, ["21.2.a"]);
+        assert!(change.value.is_none());
+        assert_eq!(change.operations, Some(vec![TextOperation::Insert { position: 0, text: "a".into() }]));
+    }
+
+    #[test]
+    fn logged_cis18_change() {
+        let change = parse(r#"{"event": "text_change", "data": {"control_id": "1", "subcontrol_id": "1.1", "field": "plan", "value": "x"}}"#);
+        assert_eq!(change.ids, ["1", "1.1"]);
+        assert_eq!(change.field, "plan");
+    }
+
+    #[test]
+    fn logged_changes_without_subcontrols() {
+        let measure = parse(r#"{"event": "dropdown_change", "data": {"measure_id": "21.2.a", "field": "status", "value": "implemented"}}"#);
+        assert_eq!(measure.ids, ["21.2.a"]);
+        let subcategory = parse(r#"{"event": "text_change", "data": {"subcategory_id": "GV.OC-01", "field": "plan", "operations": []}}"#);
+        assert_eq!(subcategory.ids, ["GV.OC-01"]);
+    }
+
+    #[test]
+    fn logged_as_ids() {
+        let change = parse(r#"{"event": "text_change", "data": {"control_id": "1", "subcontrol_id": "1.1", "field": "plan", "value": "x"}}"#);
+        let logged = serde_json::to_value(&change).unwrap();
+        assert_eq!(logged, serde_json::json!({ "ids": ["1", "1.1"], "field": "plan", "value": "x" }));
+    }
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+
+// a comment left on an item of a case, found through ids the same way as the field of a change.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(from = "CommentData")]
+pub struct Comment {
+    pub comment_id: String,
+    pub case_id: String,
+    pub ids: Vec<String>,
+    pub user_id: String,
+    pub text: String,
+    pub created_at: i64
+}
+
+// a comment as it is stored. comments stored before comments took ids name their item through a control id and
+// an optional subcontrol id.
+#[derive(Deserialize)]
+struct CommentData {
+    comment_id: String,
+    case_id: String,
+    #[serde(default)]
+    ids: Vec<String>,
+    #[serde(default)]
+    control_id: Option<String>,
+    #[serde(default)]
+    subcontrol_id: Option<String>,
+    user_id: String,
+    text: String,
+    created_at: i64
+}
+
+impl From<CommentData> for Comment {
+    fn from(data: CommentData) -> Self {
+        let ids = match data.ids.is_empty() {
+            true => data.control_id.into_iter().chain(data.subcontrol_id).collect(),
+            false => data.ids
+        };
+        Comment { comment_id: data.comment_id, case_id: data.case_id, ids, user_id: data.user_id, text: data.text, created_at: data.created_at }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comment_with_ids() {
+        let comment: Comment = serde_json::from_str(r#"{"comment_id": "c", "case_id": "case", "ids": ["1", "1.1"], "user_id": "u", "text": "t", "created_at": 0}"#).unwrap();
+        assert_eq!(comment.ids, vec!["1", "1.1"]);
+    }
+
+    #[test]
+    fn stored_comments_name_their_item_as_ids() {
+        let comment: Comment = serde_json::from_str(r#"{"comment_id": "c", "case_id": "case", "control_id": "1", "subcontrol_id": "1.1", "user_id": "u", "text": "t", "created_at": 0}"#).unwrap();
+        assert_eq!(comment.ids, vec!["1", "1.1"]);
+        let comment: Comment = serde_json::from_str(r#"{"comment_id": "c", "case_id": "case", "control_id": "5.1", "user_id": "u", "text": "t", "created_at": 0}"#).unwrap();
+        assert_eq!(comment.ids, vec!["5.1"]);
+    }
+}
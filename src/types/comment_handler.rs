@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+
+// the ids lead to the item the comment is left on, outermost first.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateCommentBody {
+    pub ids: Vec<String>,
+    pub text: String
+}
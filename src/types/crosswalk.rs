@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::case_database::Documentation;
+
+
+// how the controls of one framework map onto the items of another, as read from a crosswalk data file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Crosswalk {
+    pub source: String,
+    pub target: String,
+    #[serde(default)]
+    pub description: String,
+    pub mappings: Vec<Mapping>
+}
+
+// a source id is either a control, standing for all of its subcontrols, or a single subcontrol.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Mapping {
+    pub source: String,
+    pub targets: Vec<String>
+}
+
+// what a case says about an item of another framework, through the controls mapped onto it.
+// scores are given as a share of the source framework's scale, None if nothing mapped onto the item is applicable.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProjectedItem {
+    pub id: String,
+    pub sources: Vec<String>,
+    pub current: Option<f64>,
+    pub target: Option<f64>,
+    pub not_applicable: bool,
+    pub observation: String,
+    pub plan: String,
+    pub evidence: Vec<Documentation>
+}
+
+impl ProjectedItem {
+    // the implementation status the current score amounts to, for frameworks tracking status rather than scores.
+    pub fn status(&self) -> &'static str {
+        match self.current {
+            Some(v) if v >= IMPLEMENTED_SHARE => "implemented",
+            Some(v) if v > 0.0 => "in_progress",
+            _ => "not_started"
+        }
+    }
+}
+
+// the share of the scale from which a control counts as implemented, 4 out of 5 on the cis maturity scale.
+const IMPLEMENTED_SHARE: f64 = 0.8;
@@ -0,0 +1,153 @@
+use mongodb::bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+
+
+// what kind of value an editable field holds, which decides how changes to it are validated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    // free text, the only kind that can be edited through text operations
+    Text,
+    // one of the choices the template of the case lists for the field
+    Choice,
+    // a whole number within the range the template of the case gives for the field
+    Score,
+    Documents
+}
+
+// the values a choice or score field can take, as listed by the template a case is created from.
+// e.g. `"field_values": { "soa": ["", "applicable"], "as_is_score": { "min": 0, "max": 5 } }`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum FieldValues {
+    Choices(Vec<String>),
+    Range { min: i32, max: i32 }
+}
+
+// where a field sits in a case document, as the arrays leading to it, each with the id of the item to follow.
+// e.g. a cis18 subcontrol field is found through the controls array and then the subcontrols array.
+#[derive(Debug, Clone)]
+pub struct FieldLocation {
+    pub arrays: Vec<(&'static str, String)>,
+    pub field: String
+}
+
+impl FieldLocation {
+    // the path of the field, used to tell whether two changes touch the same field.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for (array, id) in &self.arrays {
+            path.push_str(&format!("{}.{}.", array, id));
+        }
+        path.push_str(&self.field);
+        path
+    }
+
+    // the conditions a case has to meet for every item on the way to the field to exist.
+    pub fn filter(&self) -> Document {
+        let mut filter = Document::new();
+        let mut prefix = String::new();
+        for (array, id) in &self.arrays {
+            prefix.push_str(array);
+            filter.insert(format!("{}.id", prefix), id);
+            prefix.push('.');
+        }
+        filter
+    }
+
+    // the key to set the field through, using a filtered positional operator for each array.
+    pub fn update_key(&self) -> String {
+        let mut key = String::new();
+        for (i, (array, _)) in self.arrays.iter().enumerate() {
+            key.push_str(&format!("{}.$[item{}].", array, i));
+        }
+        key.push_str(&self.field);
+        key
+    }
+
+    // the array filters matching the positional operators of the update key.
+    pub fn array_filters(&self) -> Vec<Document> {
+        self.arrays.iter()
+            .enumerate()
+            .map(|(i, (_, id))| doc! { format!("item{}.id", i): id })
+            .collect()
+    }
+
+    // the current value of the field in a case document, None if an item on the way doesn't exist
+    // and a null value if the item has no such field.
+    pub fn read(&self, case: &Document) -> Option<Bson> {
+        let mut item = case;
+        for (array, id) in &self.arrays {
+            item = item.get_array(array).ok()?
+                .iter()
+                .filter_map(Bson::as_document)
+                .find(|v| v.get_str("id") == Ok(id.as_str()))?;
+        }
+        Some(item.get(&self.field).cloned().unwrap_or(Bson::Null))
+    }
+}
+
+// the choices made when creating a case, not every framework uses all of them.
+#[derive(Debug, Default)]
+pub struct CaseOptions {
+    pub implementation_group: Option<i32>,
+    pub keep_out_of_scope: bool
+}
+
+// how far a case has come, per control and overall, on a scale from 0 to max.
+// current and target are None when nothing in scope has been scored, and the gap between them is None
+// for frameworks without targets. coverage is the share of the scale reached, for frameworks that weigh their items.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CaseScore {
+    pub max: f64,
+    pub current: Option<f64>,
+    pub target: Option<f64>,
+    pub gap: Option<f64>,
+    pub coverage: Option<f64>,
+    pub controls: Vec<ControlScore>,
+    pub largest_gaps: Vec<ItemGap>
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ControlScore {
+    pub id: String,
+    pub title: String,
+    pub current: Option<f64>,
+    pub target: Option<f64>,
+    pub gap: Option<f64>
+}
+
+// a single item of a case that falls short of its target.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ItemGap {
+    pub control_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subcontrol_id: Option<String>,
+    pub title: String,
+    pub current: f64,
+    pub target: f64,
+    pub gap: f64
+}
+
+// what moving a case onto another version of its template changes.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MigrationReport {
+    pub added: Vec<MigrationItem>,
+    pub removed: Vec<MigrationItem>,
+    pub renamed: Vec<MigrationItem>,
+    // how many items kept what was entered on them
+    pub carried_over: usize
+}
+
+// an item of the case touched by a migration. a removed item names the added item with the same title, if any,
+// as it was likely renumbered, and a renamed item keeps its id under a new title.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MigrationItem {
+    pub control_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subcontrol_id: Option<String>,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replaced_by: Option<String>
+}
@@ -1,6 +1,16 @@
+pub mod action_database;
+pub mod action_handler;
+pub mod audit_database;
 pub mod case_database;
 pub mod case_handler;
 pub mod collaboration_handler;
+pub mod comment_database;
+pub mod comment_handler;
+pub mod crosswalk;
+pub mod framework;
+pub mod role_database;
+pub mod role_handler;
+pub mod template_database;
 pub mod user_service;
 pub mod token_service;
 
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+
+// what a member of a case's group can do on the case, each role allowing everything the roles before it do.
+// viewers can read the case and follow it over the collaboration socket, commenters can comment on it,
+// editors can change it, and owners can rename, delete and share it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    Commenter,
+    Editor,
+    Owner
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Commenter => "commenter",
+            Self::Editor => "editor",
+            Self::Owner => "owner"
+        }
+    }
+}
+
+// the role a user has on a case, stored with the group of the case.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RoleAssignment {
+    pub case_id: String,
+    pub group_id: String,
+    pub user_id: String,
+    pub role: Role
+}
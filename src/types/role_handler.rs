@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::role_database::Role;
+
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RoleBody {
+    pub role: Role
+}
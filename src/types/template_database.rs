@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+
+// what identifies a template, without its content. templates stored before versioning have neither version nor locale.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TemplateMetadata {
+    pub framework: String,
+    #[serde(default)]
+    pub version: Option<i32>,
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<i64>
+}
@@ -2,11 +2,31 @@ use serde::{Deserialize, Serialize};
 
 
 
+// the body of `POST {USER_SERVICE_DOMAIN}/api/internal/check_user`, which answers with a success status if the token
+// is valid and a CheckTokenErrorResponse otherwise. the body of a success is not read.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CheckTokenBody {
     pub token: String
 }
 
+// the user a token belongs to. user tokens are jwts issued by the user service, the subject being the user id and
+// the groups claim, `groups` unless USER_TOKEN_GROUPS_CLAIM says otherwise, listing the ids of the user's groups.
+// tokens without the groups claim are rejected.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct User {
+    pub user_id: String,
+    // the groups the user is a member of, which decide the cases the user can reach. they are the groups as of when
+    // the token was issued, a user added to or removed from a group is only seen as such with a token issued since.
+    // accepted tokens are cached for up to 5 minutes, so revoking a token to drop stale groups takes that long to apply
+    pub groups: Vec<String>
+}
+
+impl User {
+    pub fn is_member(&self, group_id: &str) -> bool {
+        self.groups.iter().any(|v| v == group_id)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CheckTokenErrorResponse {
     pub error: String